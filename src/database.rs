@@ -0,0 +1,424 @@
+use crate::gamepad::PadButton;
+use crate::movie::MovieSettings;
+use crate::settings::Settings;
+use eframe::egui::Key;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Platforms whose programs the CHIP-8 core runs as intended
+const SUPPORTED_PLATFORMS: [&str; 2] = ["originalChip8", "modernChip8"];
+
+/// Controller button and extra host key for each key role the database
+/// lists. The host keys are not in the default keymap.
+const ROLES: [(&str, PadButton, Key); 6] = [
+    ("up", PadButton::DPadUp, Key::ArrowUp),
+    ("down", PadButton::DPadDown, Key::ArrowDown),
+    ("left", PadButton::DPadLeft, Key::ArrowLeft),
+    ("right", PadButton::DPadRight, Key::ArrowRight),
+    ("a", PadButton::South, Key::Space),
+    ("b", PadButton::East, Key::Enter),
+];
+
+/// How the CHIP-8 core behaves for the quirks it does not make configurable
+const CORE_QUIRKS: Quirks = Quirks {
+    shift: Some(false),
+    memory_increment_by_x: Some(false),
+    memory_leave_i_unchanged: Some(false),
+    wrap: Some(true),
+    jump: Some(false),
+    vblank: None,
+    logic: Some(true),
+};
+
+#[derive(Debug, Error)]
+pub enum DatabaseError {
+    #[error("I/O error on ROM database file '{path:?}': {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid ROM database file '{path:?}': {source}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// ROM metadata in the format of the community chip-8-database: a list of
+/// programs, an index from ROM SHA-1 to program, and the platforms they
+/// were written for
+#[derive(Debug, Default)]
+pub struct Database {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>,
+    platforms: Vec<Platform>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    /// Instructions per 60 Hz frame
+    tickrate: Option<u32>,
+    /// Quirks that differ from the platform's, by platform id
+    #[serde(default)]
+    quirky_platforms: HashMap<String, Quirks>,
+    /// Hex key for each role (`up`, `a`, `player2Left`, ...)
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Platform {
+    id: String,
+    name: String,
+    default_tickrate: u32,
+    #[serde(default)]
+    quirks: Quirks,
+}
+
+/// Behaviour that differs between CHIP-8 interpreters, `None` when unknown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VX instead of VY
+    pub shift: Option<bool>,
+    /// FX55/FX65 increment I by X only
+    pub memory_increment_by_x: Option<bool>,
+    /// FX55/FX65 leave I unchanged
+    pub memory_leave_i_unchanged: Option<bool>,
+    /// Sprites wrap around the screen edges instead of being clipped
+    pub wrap: Option<bool>,
+    /// BXNN jumps to XNN + VX
+    pub jump: Option<bool>,
+    /// DXYN waits for vertical blank
+    pub vblank: Option<bool>,
+    /// 8XY1/8XY2/8XY3 reset VF
+    pub logic: Option<bool>,
+}
+
+impl Quirks {
+    /// `self` with the known values of `overrides` replacing its own
+    fn merge(self, overrides: Quirks) -> Quirks {
+        Quirks {
+            shift: overrides.shift.or(self.shift),
+            memory_increment_by_x: overrides
+                .memory_increment_by_x
+                .or(self.memory_increment_by_x),
+            memory_leave_i_unchanged: overrides
+                .memory_leave_i_unchanged
+                .or(self.memory_leave_i_unchanged),
+            wrap: overrides.wrap.or(self.wrap),
+            jump: overrides.jump.or(self.jump),
+            vblank: overrides.vblank.or(self.vblank),
+            logic: overrides.logic.or(self.logic),
+        }
+    }
+
+    fn named(&self) -> [(&'static str, Option<bool>); 7] {
+        [
+            ("shift", self.shift),
+            ("memoryIncrementByX", self.memory_increment_by_x),
+            ("memoryLeaveIUnchanged", self.memory_leave_i_unchanged),
+            ("wrap", self.wrap),
+            ("jump", self.jump),
+            ("vblank", self.vblank),
+            ("logic", self.logic),
+        ]
+    }
+}
+
+/// What the database knows about one ROM
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// Name of the platform the ROM is configured for
+    pub platform: String,
+    /// Whether the CHIP-8 core emulates that platform
+    pub supported: bool,
+    /// Instructions per 60 Hz frame
+    pub tickrate: u32,
+    pub quirks: Quirks,
+    pub keys: BTreeMap<String, u8>,
+}
+
+impl Database {
+    /// The database shipped with the emulator
+    pub fn bundled() -> Result<Self, DatabaseError> {
+        let bundled = Path::new("<bundled>");
+        Ok(Self::index(
+            parse(bundled, include_str!("../assets/database/programs.json"))?,
+            parse(bundled, include_str!("../assets/database/sha1-hashes.json"))?,
+            parse(bundled, include_str!("../assets/database/platforms.json"))?,
+        ))
+    }
+
+    /// Read `programs.json`, `sha1-hashes.json` and `platforms.json` from a
+    /// directory
+    pub fn load_dir(dir: &Path) -> Result<Self, DatabaseError> {
+        let read = |name: &str| {
+            let path = dir.join(name);
+            std::fs::read_to_string(&path)
+                .map(|text| (path.clone(), text))
+                .map_err(|source| DatabaseError::Io { path, source })
+        };
+        let (programs_path, programs) = read("programs.json")?;
+        let (hashes_path, hashes) = read("sha1-hashes.json")?;
+        let (platforms_path, platforms) = read("platforms.json")?;
+        Ok(Self::index(
+            parse(&programs_path, &programs)?,
+            parse(&hashes_path, &hashes)?,
+            parse(&platforms_path, &platforms)?,
+        ))
+    }
+
+    /// Where a copy of the community database is picked up from, next to
+    /// the settings file
+    pub fn user_dir() -> Option<PathBuf> {
+        Some(Settings::path().ok()?.parent()?.join("database"))
+    }
+
+    /// A copy of the community database in `dir` if there is one, the
+    /// bundled database otherwise
+    pub fn load(dir: Option<&Path>) -> Result<Self, DatabaseError> {
+        match dir {
+            Some(dir) if dir.join("programs.json").exists() => Self::load_dir(dir),
+            _ => Self::bundled(),
+        }
+    }
+
+    fn index(
+        programs: Vec<Program>,
+        hashes: HashMap<String, usize>,
+        platforms: Vec<Platform>,
+    ) -> Self {
+        let hashes = hashes
+            .into_iter()
+            .map(|(sha1, program)| (sha1.to_ascii_lowercase(), program))
+            .collect();
+        Self {
+            programs,
+            hashes,
+            platforms,
+        }
+    }
+
+    pub fn lookup(&self, sha1: &str) -> Option<RomInfo> {
+        let sha1 = sha1.to_ascii_lowercase();
+        let program = self.programs.get(*self.hashes.get(&sha1)?)?;
+        let rom = program
+            .roms
+            .iter()
+            .find(|(hash, _)| hash.eq_ignore_ascii_case(&sha1))
+            .map(|(_, rom)| rom)?;
+
+        // Prefer a platform the core emulates
+        let platform_id = rom
+            .platforms
+            .iter()
+            .find(|id| SUPPORTED_PLATFORMS.contains(&id.as_str()))
+            .or(rom.platforms.first())?;
+        let platform = self.platforms.iter().find(|p| &p.id == platform_id)?;
+        let quirks = rom
+            .quirky_platforms
+            .get(platform_id)
+            .map_or(platform.quirks, |&overrides| {
+                platform.quirks.merge(overrides)
+            });
+
+        Some(RomInfo {
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            platform: platform.name.clone(),
+            supported: SUPPORTED_PLATFORMS.contains(&platform_id.as_str()),
+            tickrate: rom.tickrate.unwrap_or(platform.default_tickrate),
+            quirks,
+            keys: rom.keys.clone(),
+        })
+    }
+}
+
+fn parse<T: DeserializeOwned>(path: &Path, text: &str) -> Result<T, DatabaseError> {
+    serde_json::from_str(text).map_err(|source| DatabaseError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+impl RomInfo {
+    /// `base` with the recommended speed and the quirks the core can change
+    pub fn profile(&self, base: MovieSettings) -> MovieSettings {
+        MovieSettings {
+            frequency: self.tickrate * 60,
+            vip_timing: false,
+            display_wait: self.quirks.vblank.unwrap_or(base.display_wait),
+            ..base
+        }
+    }
+
+    /// Quirks the ROM expects that the core does not emulate
+    pub fn unsupported_quirks(&self) -> Vec<&'static str> {
+        self.quirks
+            .named()
+            .into_iter()
+            .zip(CORE_QUIRKS.named())
+            .filter_map(|((name, wanted), (_, core))| match (wanted, core) {
+                (Some(wanted), Some(core)) if wanted != core => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Controller buttons for the key roles the database lists
+    pub fn gamepad_bindings(&self) -> Vec<(PadButton, usize)> {
+        self.roles()
+            .map(|(pad_button, _, key)| (pad_button, key))
+            .collect()
+    }
+
+    /// Arrow keys, Space and Enter for the key roles the database lists
+    pub fn key_bindings(&self) -> Vec<(Key, usize)> {
+        self.roles().map(|(_, host, key)| (host, key)).collect()
+    }
+
+    fn roles(&self) -> impl Iterator<Item = (PadButton, Key, usize)> + '_ {
+        ROLES.into_iter().filter_map(|(role, pad_button, host)| {
+            self.keys
+                .get(role)
+                .map(|&key| (pad_button, host, key as usize & 0xF))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAMS: &str = r#"[
+        {
+            "title": "Maze Runner",
+            "authors": ["Someone"],
+            "release": "2020",
+            "roms": {
+                "AAAA000000000000000000000000000000000000": {
+                    "file": "maze.ch8",
+                    "platforms": ["superchip", "originalChip8"],
+                    "tickrate": 20,
+                    "quirkyPlatforms": { "originalChip8": { "vblank": false, "wrap": true } },
+                    "keys": { "up": 5, "down": 8, "a": 6, "player2Up": 14 }
+                },
+                "bbbb000000000000000000000000000000000000": {
+                    "platforms": ["superchip"]
+                }
+            }
+        }
+    ]"#;
+    const HASHES: &str = r#"{
+        "AAAA000000000000000000000000000000000000": 0,
+        "bbbb000000000000000000000000000000000000": 0
+    }"#;
+
+    fn database() -> Database {
+        let bundled: Vec<Platform> =
+            serde_json::from_str(include_str!("../assets/database/platforms.json")).unwrap();
+        Database::index(
+            serde_json::from_str(PROGRAMS).unwrap(),
+            serde_json::from_str(HASHES).unwrap(),
+            bundled,
+        )
+    }
+
+    #[test]
+    fn bundled_database_parses() {
+        let database = Database::bundled().unwrap();
+        assert!(database.platforms.iter().any(|p| p.id == "originalChip8"));
+    }
+
+    #[test]
+    fn bundled_hashes_resolve_to_programs() {
+        let database = Database::bundled().unwrap();
+        for sha1 in database.hashes.keys() {
+            assert!(database.lookup(sha1).is_some(), "{sha1} has no program");
+        }
+    }
+
+    #[test]
+    fn lookup_prefers_supported_platforms_and_applies_overrides() {
+        let info = database()
+            .lookup("aaaa000000000000000000000000000000000000")
+            .unwrap();
+        assert_eq!(info.title, "Maze Runner");
+        assert_eq!(info.platform, "Cosmac VIP CHIP-8");
+        assert!(info.supported);
+        assert_eq!(info.tickrate, 20);
+        assert_eq!(info.quirks.vblank, Some(false));
+        assert_eq!(info.quirks.logic, Some(true));
+        assert!(info.unsupported_quirks().is_empty());
+
+        let profile = info.profile(MovieSettings {
+            display_wait: true,
+            vip_timing: true,
+            ..MovieSettings::default()
+        });
+        assert_eq!(profile.frequency, 1200);
+        assert!(!profile.vip_timing);
+        assert!(!profile.display_wait);
+        assert_eq!(profile.seed, MovieSettings::default().seed);
+    }
+
+    #[test]
+    fn other_platforms_report_their_quirks() {
+        let info = database()
+            .lookup("BBBB000000000000000000000000000000000000")
+            .unwrap();
+        assert!(!info.supported);
+        assert_eq!(info.tickrate, 30);
+        assert_eq!(
+            info.unsupported_quirks(),
+            ["shift", "memoryLeaveIUnchanged", "wrap", "jump", "logic"]
+        );
+    }
+
+    #[test]
+    fn keys_map_to_controller_buttons_and_host_keys() {
+        let info = database()
+            .lookup("aaaa000000000000000000000000000000000000")
+            .unwrap();
+        assert_eq!(
+            info.gamepad_bindings(),
+            [
+                (PadButton::DPadUp, 5),
+                (PadButton::DPadDown, 8),
+                (PadButton::South, 6)
+            ]
+        );
+        assert_eq!(
+            info.key_bindings(),
+            [(Key::ArrowUp, 5), (Key::ArrowDown, 8), (Key::Space, 6)]
+        );
+    }
+
+    #[test]
+    fn unknown_roms_are_not_found() {
+        assert_eq!(database().lookup("cccc"), None);
+    }
+}
@@ -0,0 +1,178 @@
+/// How consecutive frames are combined before display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterMode {
+    /// Show frames as the emulator produces them
+    #[default]
+    Off,
+    /// Pixels fade out over several frames like a CRT phosphor
+    Phosphor,
+    /// A pixel is lit if it was lit in either of the last two frames
+    Or,
+}
+
+impl FilterMode {
+    pub const ALL: [FilterMode; 3] = [FilterMode::Off, FilterMode::Phosphor, FilterMode::Or];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilterMode::Off => "Off",
+            FilterMode::Phosphor => "Phosphor",
+            FilterMode::Or => "OR last two frames",
+        }
+    }
+}
+
+/// Anti-flicker filter between the emulator framebuffer and the screen.
+///
+/// CHIP-8 games erase sprites by XOR-drawing them again, so moving objects
+/// are invisible in every other frame. Blending with previous frames hides
+/// that.
+#[derive(Debug, Clone)]
+pub struct DisplayFilter {
+    pub mode: FilterMode,
+    /// Fraction of brightness a phosphor pixel keeps per 60 Hz tick
+    pub decay: f32,
+    /// Previous raw frame, for [`FilterMode::Or`]
+    previous: Vec<u32>,
+    /// Per-channel brightness, for [`FilterMode::Phosphor`]
+    glow: Vec<[f32; 3]>,
+    output: Vec<u32>,
+}
+
+impl Default for DisplayFilter {
+    fn default() -> Self {
+        Self {
+            mode: FilterMode::Off,
+            decay: 0.6,
+            previous: Vec::new(),
+            glow: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl DisplayFilter {
+    /// Whether the output keeps changing without new frames, i.e. phosphor
+    /// pixels are still fading or the OR of the last two frames still shows
+    /// pixels that are gone
+    pub fn is_fading(&self) -> bool {
+        match self.mode {
+            FilterMode::Off => false,
+            FilterMode::Or => self.output != self.previous,
+            FilterMode::Phosphor => {
+                self.glow.iter().zip(&self.previous).any(|(glow, &frame)| {
+                    glow.iter().zip(channels(frame)).any(|(&g, c)| g as u8 > c)
+                })
+            }
+        }
+    }
+
+    /// Combine `frame` (ARGB8888) with the previous ones. Called once per
+    /// displayed frame, with the emulated 60 Hz `ticks` since the previous
+    /// call, so phosphor fades take the same time at any refresh rate.
+    pub fn apply(&mut self, frame: &[u32], ticks: u32) -> &[u32] {
+        if self.output.len() != frame.len() {
+            self.previous = frame.to_vec();
+            self.glow = frame
+                .iter()
+                .map(|&argb| channels(argb).map(f32::from))
+                .collect();
+            self.output = frame.to_vec();
+        }
+
+        match self.mode {
+            FilterMode::Off => self.output.copy_from_slice(frame),
+            FilterMode::Or => {
+                for ((out, &new), &old) in self.output.iter_mut().zip(frame).zip(&self.previous) {
+                    *out = brightest(new, old);
+                }
+            }
+            FilterMode::Phosphor => {
+                let decay = self.decay.powi(ticks.min(i32::MAX as u32) as i32);
+                for ((out, glow), &new) in self.output.iter_mut().zip(&mut self.glow).zip(frame) {
+                    for (g, c) in glow.iter_mut().zip(channels(new)) {
+                        *g = (*g * decay).max(c as f32);
+                    }
+                    *out = (new & 0xFF00_0000)
+                        | (glow[0] as u32) << 16
+                        | (glow[1] as u32) << 8
+                        | glow[2] as u32;
+                }
+            }
+        }
+
+        if self.mode != FilterMode::Phosphor {
+            // Start fading from the current frame when switching to phosphor
+            for (glow, &new) in self.glow.iter_mut().zip(frame) {
+                *glow = channels(new).map(f32::from);
+            }
+        }
+        self.previous.copy_from_slice(frame);
+        &self.output
+    }
+}
+
+/// Red, green and blue channels of an ARGB8888 pixel
+fn channels(argb: u32) -> [u8; 3] {
+    [(argb >> 16) as u8, (argb >> 8) as u8, argb as u8]
+}
+
+/// Per-channel maximum of two ARGB8888 pixels
+fn brightest(a: u32, b: u32) -> u32 {
+    let [ar, ag, ab] = channels(a);
+    let [br, bg, bb] = channels(b);
+    (a & 0xFF00_0000) | (ar.max(br) as u32) << 16 | (ag.max(bg) as u32) << 8 | ab.max(bb) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ON: u32 = 0xFFFF_FFFF;
+    const OFF: u32 = 0xFF00_0000;
+
+    #[test]
+    fn or_clears_ghosts_on_the_next_frame() {
+        let mut filter = DisplayFilter {
+            mode: FilterMode::Or,
+            ..Default::default()
+        };
+        filter.apply(&[ON, OFF], 1);
+        assert!(!filter.is_fading());
+
+        // Cleared screen: the old frame still shows once
+        assert_eq!(filter.apply(&[OFF, OFF], 1), [ON, OFF]);
+        assert!(filter.is_fading());
+        assert_eq!(filter.apply(&[OFF, OFF], 1), [OFF, OFF]);
+        assert!(!filter.is_fading());
+    }
+
+    #[test]
+    fn phosphor_fades_per_tick_not_per_repaint() {
+        let phosphor = || DisplayFilter {
+            mode: FilterMode::Phosphor,
+            decay: 0.5,
+            ..Default::default()
+        };
+        // One tick shown at 60 Hz, and over several repaints at 144 Hz
+        let mut at_60 = phosphor();
+        let mut at_144 = phosphor();
+        at_60.apply(&[ON], 0);
+        at_144.apply(&[ON], 0);
+        let once = at_60.apply(&[OFF], 1).to_vec();
+        at_144.apply(&[OFF], 0);
+        at_144.apply(&[OFF], 1);
+        assert_eq!(at_144.apply(&[OFF], 0), once);
+        assert_eq!(once, [0xFF7F_7F7F]);
+
+        // Catching up several ticks at once fades as far as one at a time
+        let mut stepped = phosphor();
+        let mut skipped = phosphor();
+        stepped.apply(&[ON], 0);
+        skipped.apply(&[ON], 0);
+        for _ in 0..3 {
+            stepped.apply(&[OFF], 1);
+        }
+        assert_eq!(skipped.apply(&[OFF], 3), stepped.apply(&[OFF], 0));
+    }
+}
@@ -0,0 +1,7 @@
+pub mod filter;
+pub mod palette;
+pub mod postprocess;
+
+pub use filter::{DisplayFilter, FilterMode};
+pub use palette::Palette;
+pub use postprocess::PostProcess;
@@ -0,0 +1,124 @@
+use crate::emulators::chip8::configs::DEFAULT_PALETTE;
+use serde::{Deserialize, Serialize};
+
+/// Four display colours (ARGB8888), in XO-CHIP plane order: background,
+/// plane 1, plane 2 and both planes. Plain CHIP-8 only uses the first two.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Palette {
+    pub name: String,
+    #[serde(with = "hex_colors")]
+    pub colors: [u32; 4],
+}
+
+/// Name used for palettes edited by hand
+pub const CUSTOM: &str = "Custom";
+
+impl Palette {
+    fn preset(name: &str, colors: [u32; 4]) -> Self {
+        Self {
+            name: name.to_string(),
+            colors,
+        }
+    }
+
+    /// Built-in palettes
+    pub fn presets() -> Vec<Palette> {
+        vec![
+            Self::preset("Classic", DEFAULT_PALETTE),
+            Self::preset(
+                "Green phosphor",
+                [0xFF0A1A0A, 0xFF33FF66, 0xFF1A8033, 0xFF99FFB3],
+            ),
+            Self::preset("Amber", [0xFF1A0F00, 0xFFFFB000, 0xFF805800, 0xFFFFD580]),
+            Self::preset("Game Boy", [0xFF9BBC0F, 0xFF0F380F, 0xFF8BAC0F, 0xFF306230]),
+            Self::preset("Octo", [0xFF996600, 0xFFFFCC00, 0xFFFF6600, 0xFF662200]),
+        ]
+    }
+
+    /// Default palette for a system, by [`crate::emulators::Emulator::system_name`]
+    pub fn default_for(system: &str) -> Palette {
+        let name = match system {
+            "Game Boy" => "Game Boy",
+            _ => "Classic",
+        };
+        Self::presets()
+            .into_iter()
+            .find(|palette| palette.name == name)
+            .expect("default palette is a preset")
+    }
+}
+
+/// Format an ARGB8888 colour as `#RRGGBB`
+pub fn to_hex(argb: u32) -> String {
+    format!("#{:06X}", argb & 0x00FF_FFFF)
+}
+
+/// Parse `#RRGGBB` or `RRGGBB` into an opaque ARGB8888 colour
+pub fn parse_hex(text: &str) -> Option<u32> {
+    let digits = text.trim().trim_start_matches('#');
+    // from_str_radix would also take a sign
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(digits, 16)
+        .ok()
+        .map(|rgb| 0xFF00_0000 | rgb)
+}
+
+/// Colours are stored as `#RRGGBB` strings so the settings file is easy to edit
+mod hex_colors {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(colors: &[u32; 4], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(colors.iter().map(|&color| super::to_hex(color)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u32; 4], D::Error> {
+        let texts = <[String; 4]>::deserialize(deserializer)?;
+        let mut colors = [0; 4];
+        for (color, text) in colors.iter_mut().zip(&texts) {
+            *color = super::parse_hex(text)
+                .ok_or_else(|| D::Error::custom(format!("invalid colour '{}'", text)))?;
+        }
+        Ok(colors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colours_round_trip() {
+        for palette in Palette::presets() {
+            for color in palette.colors {
+                assert_eq!(parse_hex(&to_hex(color)), Some(color));
+            }
+        }
+        assert_eq!(to_hex(0xFF12AB0F), "#12AB0F");
+        assert_eq!(parse_hex(" #12ab0f "), Some(0xFF12AB0F));
+        assert_eq!(parse_hex("12AB0F"), Some(0xFF12AB0F));
+    }
+
+    #[test]
+    fn malformed_hex_is_rejected() {
+        for text in [
+            "", "#", "#12AB0", "#12AB0F0", "+12AB0", "#+12AB0", "#-12AB0", "#12 AB0",
+        ] {
+            assert_eq!(parse_hex(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn palettes_round_trip_through_toml() {
+        let palette = Palette::default_for("CHIP-8");
+        assert_eq!(palette.colors, DEFAULT_PALETTE);
+        let text = toml::to_string(&palette).unwrap();
+        assert!(text.contains("\"#AAAAAA\""), "{}", text);
+        assert_eq!(toml::from_str::<Palette>(&text).unwrap(), palette);
+
+        let invalid = text.replace("#AAAAAA", "+AAAAA");
+        assert!(toml::from_str::<Palette>(&invalid).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+/// Upscaling factor used when any effect is enabled, so scanlines and the
+/// pixel grid have sub-pixel rows and columns to work with
+pub const EFFECT_SCALE: usize = 4;
+
+/// Display scale range, small enough at the top to leave room for the panels
+const MIN_DISPLAY_SCALE: f32 = 2.0;
+const MAX_DISPLAY_SCALE: f32 = 12.0;
+
+/// CPU-side post-processing applied to the frame before upload, so it works
+/// without any GPU shader support
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PostProcess {
+    /// Only scale the display by whole multiples, keeping pixels square
+    pub integer_scale: bool,
+    /// Darken every last sub-row of a pixel like CRT scanlines
+    pub scanlines: bool,
+    /// Darken the edges of every pixel
+    pub pixel_grid: bool,
+    /// Bend the image like a curved CRT screen, with dark corners
+    pub curvature: bool,
+    /// Let bright pixels glow into their neighbours
+    pub bloom: bool,
+}
+
+/// A processed image, ARGB8888
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl PostProcess {
+    /// Whether any effect changes the image (integer scaling only affects layout)
+    pub fn is_active(&self) -> bool {
+        self.scanlines || self.pixel_grid || self.curvature || self.bloom
+    }
+
+    /// Scale to show the display at, given the largest one that fits
+    pub fn display_scale(&self, fit: f32) -> f32 {
+        let scale = fit.clamp(MIN_DISPLAY_SCALE, MAX_DISPLAY_SCALE);
+        if self.integer_scale {
+            scale.floor()
+        } else {
+            scale
+        }
+    }
+
+    pub fn apply(&self, frame: &[u32], width: usize, height: usize) -> Image {
+        if !self.is_active() {
+            return Image {
+                width,
+                height,
+                pixels: frame.to_vec(),
+            };
+        }
+
+        let mut image = upscale(frame, width, height, EFFECT_SCALE);
+        if self.pixel_grid {
+            darken_where(&mut image, 0.7, |x, y| {
+                x % EFFECT_SCALE == EFFECT_SCALE - 1 || y % EFFECT_SCALE == EFFECT_SCALE - 1
+            });
+        }
+        if self.scanlines {
+            darken_where(&mut image, 0.5, |_, y| y % EFFECT_SCALE == EFFECT_SCALE - 1);
+        }
+        if self.bloom {
+            bloom(&mut image);
+        }
+        if self.curvature {
+            image = curve(&image);
+        }
+        image
+    }
+}
+
+fn upscale(frame: &[u32], width: usize, height: usize, scale: usize) -> Image {
+    let (out_width, out_height) = (width * scale, height * scale);
+    let pixels = (0..out_width * out_height)
+        .map(|i| frame[(i / out_width / scale) * width + (i % out_width) / scale])
+        .collect();
+    Image {
+        width: out_width,
+        height: out_height,
+        pixels,
+    }
+}
+
+fn channels(argb: u32) -> [f32; 3] {
+    [(argb >> 16) as u8, (argb >> 8) as u8, argb as u8].map(f32::from)
+}
+
+fn from_channels(alpha: u32, [r, g, b]: [f32; 3]) -> u32 {
+    let c = |v: f32| v.clamp(0.0, 255.0) as u32;
+    alpha | c(r) << 16 | c(g) << 8 | c(b)
+}
+
+fn scale_pixel(argb: u32, factor: f32) -> u32 {
+    from_channels(argb & 0xFF00_0000, channels(argb).map(|c| c * factor))
+}
+
+fn darken_where(image: &mut Image, factor: f32, on: impl Fn(usize, usize) -> bool) {
+    let width = image.width;
+    for (i, pixel) in image.pixels.iter_mut().enumerate() {
+        if on(i % width, i / width) {
+            *pixel = scale_pixel(*pixel, factor);
+        }
+    }
+}
+
+/// Add a box-blurred copy of the image on top of itself
+fn bloom(image: &mut Image) {
+    const RADIUS: usize = EFFECT_SCALE;
+    const STRENGTH: f32 = 0.5;
+    let (width, height) = (image.width, image.height);
+    let source: Vec<[f32; 3]> = image.pixels.iter().map(|&p| channels(p)).collect();
+
+    // Separable blur: horizontal pass, then vertical pass
+    let blur = |input: &[[f32; 3]], horizontal: bool| -> Vec<[f32; 3]> {
+        (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                let (pos, len) = if horizontal { (x, width) } else { (y, height) };
+                let range = pos.saturating_sub(RADIUS)..(pos + RADIUS + 1).min(len);
+                let count = range.len() as f32;
+                let mut sum = [0.0; 3];
+                for p in range {
+                    let j = if horizontal {
+                        y * width + p
+                    } else {
+                        p * width + x
+                    };
+                    for (s, c) in sum.iter_mut().zip(input[j]) {
+                        *s += c;
+                    }
+                }
+                sum.map(|s| s / count)
+            })
+            .collect()
+    };
+    let glow = blur(&blur(&source, true), false);
+
+    for ((pixel, base), glow) in image.pixels.iter_mut().zip(&source).zip(&glow) {
+        let mut out = *base;
+        for (o, g) in out.iter_mut().zip(glow) {
+            *o += g * STRENGTH;
+        }
+        *pixel = from_channels(*pixel & 0xFF00_0000, out);
+    }
+}
+
+/// Barrel distortion with a vignette
+fn curve(image: &Image) -> Image {
+    const BEND: f32 = 0.08;
+    let (width, height) = (image.width, image.height);
+    let pixels = (0..width * height)
+        .map(|i| {
+            // Output position in -1..1
+            let u = (i % width) as f32 / width as f32 * 2.0 - 1.0;
+            let v = (i / width) as f32 / height as f32 * 2.0 - 1.0;
+            let bend = 1.0 + BEND * (u * u + v * v);
+            let (su, sv) = (u * bend, v * bend);
+            if su.abs() > 1.0 || sv.abs() > 1.0 {
+                return 0xFF000000;
+            }
+
+            let x = (((su + 1.0) / 2.0 * width as f32) as usize).min(width - 1);
+            let y = (((sv + 1.0) / 2.0 * height as f32) as usize).min(height - 1);
+            let vignette = 1.0 - 0.25 * (su * su * sv * sv).sqrt();
+            scale_pixel(image.pixels[y * width + x], vignette)
+        })
+        .collect();
+    Image {
+        width,
+        height,
+        pixels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_scaling_rounds_down_within_the_limits() {
+        let smooth = PostProcess::default();
+        let integer = PostProcess {
+            integer_scale: true,
+            ..Default::default()
+        };
+        assert_eq!(smooth.display_scale(5.7), 5.7);
+        assert_eq!(integer.display_scale(5.7), 5.0);
+        assert_eq!(integer.display_scale(6.0), 6.0);
+        assert_eq!(integer.display_scale(1.3), 2.0);
+        assert_eq!(integer.display_scale(30.5), 12.0);
+    }
+
+    #[test]
+    fn effects_upscale_the_frame() {
+        const ON: u32 = 0xFFFF_FFFF;
+        const OFF: u32 = 0xFF00_0000;
+        let frame = [ON, OFF, OFF, ON, ON, OFF];
+
+        let plain = PostProcess::default().apply(&frame, 3, 2);
+        assert_eq!((plain.width, plain.height), (3, 2));
+        assert_eq!(plain.pixels, frame);
+
+        let upscaled = upscale(&frame, 3, 2, EFFECT_SCALE);
+        assert_eq!((upscaled.width, upscaled.height), (12, 8));
+        assert_eq!(upscaled.pixels[0], ON);
+        assert_eq!(upscaled.pixels[EFFECT_SCALE], OFF);
+        assert_eq!(upscaled.pixels[EFFECT_SCALE * 12], ON);
+
+        let scanlines = PostProcess {
+            scanlines: true,
+            ..Default::default()
+        }
+        .apply(&frame, 3, 2);
+        assert_eq!((scanlines.width, scanlines.height), (12, 8));
+        assert_eq!(scanlines.pixels[0], ON);
+        // The last sub-row of each pixel is darkened
+        assert_eq!(scanlines.pixels[(EFFECT_SCALE - 1) * 12], 0xFF7F_7F7F);
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+/// Classification of a single byte in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteKind {
+    /// Not reached by the analysis, most likely data or dead code
+    Unknown,
+    /// First byte of an instruction
+    Code,
+    /// Second byte of an instruction
+    Operand,
+    /// Referenced through I by a DXYN, FX33, FX55 or FX65
+    Data,
+}
+
+/// Why an address was given a label, ordered by priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LabelKind {
+    Data,
+    Sprite,
+    Jump,
+    Subroutine,
+}
+
+/// A single row of a listing: an instruction (2 bytes) or a data byte
+#[derive(Debug, Clone, Copy)]
+pub struct Line {
+    pub addr: u16,
+    pub kind: ByteKind,
+}
+
+/// Result of a recursive-descent pass over a CHIP-8 program.
+///
+/// Starting from the entry point every reachable instruction is followed
+/// through jumps, calls and skips, so code and data can be told apart even
+/// when sprites are interleaved with instructions or code is odd-aligned.
+#[derive(Debug, Clone)]
+pub struct CodeMap {
+    start: u16,
+    end: u16,
+    kinds: Vec<ByteKind>,
+    labels: BTreeMap<u16, LabelKind>,
+}
+
+impl CodeMap {
+    /// Analyze `memory[start..end]`, beginning execution at `start`
+    pub fn analyze(memory: &[u8], start: u16, end: u16) -> Self {
+        let mut map = Self {
+            start,
+            end: end.min(memory.len() as u16),
+            kinds: vec![ByteKind::Unknown; memory.len()],
+            labels: BTreeMap::new(),
+        };
+        map.trace(memory, start);
+        map
+    }
+
+    /// Address range covered by the analysis
+    pub fn range(&self) -> std::ops::Range<u16> {
+        self.start..self.end
+    }
+
+    pub fn kind(&self, addr: u16) -> ByteKind {
+        self.kinds
+            .get(addr as usize)
+            .copied()
+            .unwrap_or(ByteKind::Unknown)
+    }
+
+    /// Whether a listing has a row starting at `addr`
+    pub fn is_line_start(&self, addr: u16) -> bool {
+        self.range().contains(&addr) && self.kind(addr) != ByteKind::Operand
+    }
+
+    /// Symbolic name for an address, if anything refers to it
+    pub fn label(&self, addr: u16) -> Option<String> {
+        self.labels.get(&addr).map(|kind| {
+            let prefix = match kind {
+                LabelKind::Subroutine => "sub",
+                LabelKind::Jump => "label",
+                LabelKind::Sprite => "sprite",
+                LabelKind::Data => "data",
+            };
+            format!("{}_{:03X}", prefix, addr)
+        })
+    }
+
+    /// Split the analyzed range into instructions and data bytes
+    pub fn lines(&self) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut addr = self.start;
+        while addr < self.end {
+            let kind = self.kind(addr);
+            lines.push(Line { addr, kind });
+            addr += if kind == ByteKind::Code { 2 } else { 1 };
+        }
+        lines
+    }
+
+    /// Follow execution from an additional entry point, e.g. a `JP V0, addr`
+    /// target discovered at runtime
+    pub fn trace(&mut self, memory: &[u8], entry: u16) {
+        // Each work item carries the last known value of I along that path
+        let mut work: Vec<(u16, Option<u16>)> = vec![(entry, None)];
+
+        while let Some((mut pc, mut index)) = work.pop() {
+            loop {
+                let addr = pc as usize;
+                if addr + 1 == self.end as usize && self.kinds[addr] == ByteKind::Unknown {
+                    // Half an instruction left, the listing keeps it a byte
+                    self.kinds[addr] = ByteKind::Data;
+                }
+                if addr + 1 >= self.end as usize
+                    || !matches!(self.kinds[addr], ByteKind::Unknown | ByteKind::Data)
+                    || self.kinds[addr + 1] == ByteKind::Code
+                {
+                    break;
+                }
+
+                let opcode = (memory[addr] as u16) << 8 | memory[addr + 1] as u16;
+                if !is_valid(opcode) {
+                    break;
+                }
+
+                self.kinds[addr] = ByteKind::Code;
+                self.kinds[addr + 1] = ByteKind::Operand;
+
+                let x = (opcode & 0x0F00) >> 8;
+                let n = opcode & 0x000F;
+                let nnn = opcode & 0x0FFF;
+                let next = pc.wrapping_add(2);
+
+                match opcode & 0xF000 {
+                    0x0000 if opcode == 0x00EE => break,
+                    0x1000 => {
+                        self.add_label(nnn, LabelKind::Jump);
+                        pc = nnn;
+                        continue;
+                    }
+                    0x2000 => {
+                        self.add_label(nnn, LabelKind::Subroutine);
+                        work.push((nnn, index));
+                    }
+                    0x3000 | 0x4000 | 0x5000 | 0x9000 | 0xE000 => {
+                        work.push((next.wrapping_add(2), index));
+                    }
+                    0xA000 => index = Some(nnn),
+                    // Target depends on V0, only known at runtime
+                    0xB000 => break,
+                    0xD000 => {
+                        if let Some(i) = index {
+                            self.mark_data(i, n, LabelKind::Sprite);
+                        }
+                    }
+                    0xF000 => match opcode & 0x00FF {
+                        0x1E | 0x29 => index = None,
+                        0x33 => {
+                            if let Some(i) = index {
+                                self.mark_data(i, 3, LabelKind::Data);
+                            }
+                        }
+                        0x55 | 0x65 => {
+                            if let Some(i) = index {
+                                self.mark_data(i, x + 1, LabelKind::Data);
+                            }
+                            index = None;
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+
+                pc = next;
+            }
+        }
+    }
+
+    fn add_label(&mut self, addr: u16, kind: LabelKind) {
+        let entry = self.labels.entry(addr).or_insert(kind);
+        *entry = (*entry).max(kind);
+    }
+
+    fn mark_data(&mut self, addr: u16, len: u16, kind: LabelKind) {
+        // Only label data inside the program, not the font or interpreter area
+        if !(self.start..self.end).contains(&addr) {
+            return;
+        }
+
+        self.add_label(addr, kind);
+        for a in addr..addr.saturating_add(len).min(self.end) {
+            if self.kinds[a as usize] == ByteKind::Unknown {
+                self.kinds[a as usize] = ByteKind::Data;
+            }
+        }
+    }
+}
+
+/// Whether an opcode is one the interpreter accepts. Zero words are
+/// rejected as well since they almost always mean padding.
+fn is_valid(opcode: u16) -> bool {
+    match opcode & 0xF000 {
+        0x0000 => opcode != 0x0000,
+        0x5000 | 0x9000 => opcode & 0x000F == 0,
+        0x8000 => matches!(opcode & 0x000F, 0x0..=0x7 | 0xE),
+        0xE000 => matches!(opcode & 0x00FF, 0x9E | 0xA1),
+        0xF000 => matches!(
+            opcode & 0x00FF,
+            0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x33 | 0x55 | 0x65
+        ),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulators::chip8::{asm, disasm};
+
+    const START: u16 = 0x200;
+
+    fn analyze(program: &[u8]) -> (Vec<u8>, CodeMap) {
+        let mut memory = vec![0; 4096];
+        memory[START as usize..][..program.len()].copy_from_slice(program);
+        let map = CodeMap::analyze(&memory, START, START + program.len() as u16);
+        (memory, map)
+    }
+
+    fn kinds(map: &CodeMap) -> Vec<ByteKind> {
+        map.range().map(|addr| map.kind(addr)).collect()
+    }
+
+    use ByteKind::{Code, Data, Operand, Unknown};
+
+    #[test]
+    fn jumps_are_followed_and_skip_what_they_jump_over() {
+        // JP 204, two data bytes, CLS, JP 206 (itself)
+        let (_, map) = analyze(&[0x12, 0x04, 0xF0, 0x90, 0x00, 0xE0, 0x12, 0x06]);
+        assert_eq!(
+            kinds(&map),
+            [Code, Operand, Unknown, Unknown, Code, Operand, Code, Operand]
+        );
+        assert_eq!(map.label(0x204).as_deref(), Some("label_204"));
+        assert_eq!(map.label(0x202), None);
+        let lines: Vec<_> = map.lines().iter().map(|line| line.addr).collect();
+        assert_eq!(lines, [0x200, 0x202, 0x203, 0x204, 0x206]);
+    }
+
+    #[test]
+    fn skips_follow_both_paths() {
+        // SE V0, 0; RET; RET
+        let (_, map) = analyze(&[0x30, 0x00, 0x00, 0xEE, 0x00, 0xEE]);
+        assert_eq!(kinds(&map), [Code, Operand, Code, Operand, Code, Operand]);
+    }
+
+    #[test]
+    fn calls_trace_the_subroutine_and_stop_at_ret() {
+        // CALL 206; JP 202 (itself); data; CLS; RET; data
+        let (_, map) = analyze(&[
+            0x22, 0x06, 0x12, 0x02, 0xAA, 0xBB, 0x00, 0xE0, 0x00, 0xEE, 0x12, 0x00,
+        ]);
+        assert_eq!(
+            kinds(&map),
+            [
+                Code, Operand, Code, Operand, Unknown, Unknown, Code, Operand, Code, Operand,
+                Unknown, Unknown
+            ]
+        );
+        assert_eq!(map.label(0x206).as_deref(), Some("sub_206"));
+        assert_eq!(map.label(0x202).as_deref(), Some("label_202"));
+    }
+
+    #[test]
+    fn sprites_drawn_through_i_are_data() {
+        // LD I, 206; DRW V0, V0, 2; JP 204 (itself); sprite
+        let (_, map) = analyze(&[0xA2, 0x06, 0xD0, 0x02, 0x12, 0x04, 0xF0, 0x90]);
+        assert_eq!(&kinds(&map)[6..], [Data, Data]);
+        assert_eq!(map.label(0x206).as_deref(), Some("sprite_206"));
+    }
+
+    #[test]
+    fn computed_jumps_need_their_target_traced() {
+        // JP V0, 204; RET; CLS; RET
+        let (memory, mut map) = analyze(&[0xB2, 0x04, 0x00, 0xEE, 0x00, 0xE0, 0x00, 0xEE]);
+        assert_eq!(&kinds(&map)[2..], [Unknown; 6]);
+
+        map.trace(&memory, 0x204);
+        assert_eq!(&kinds(&map)[2..4], [Unknown; 2]);
+        assert_eq!(&kinds(&map)[4..], [Code, Operand, Code, Operand]);
+    }
+
+    #[test]
+    fn odd_length_programs_end_in_a_data_byte() {
+        // CLS, then half of an LD V0 that runs off the end
+        let rom = [0x00, 0xE0, 0x60];
+        let (_, map) = analyze(&rom);
+        assert_eq!(kinds(&map), [Code, Operand, Data]);
+        assert_eq!(map.lines().len(), 2);
+
+        let listing = disasm::rom_listing(&rom);
+        assert_eq!(asm::assemble(&listing).unwrap(), rom, "{}", listing);
+    }
+}
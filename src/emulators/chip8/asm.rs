@@ -0,0 +1,755 @@
+use super::configs::PROGRAM_START_ADDRESS;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Largest program that fits between the program start and the end of memory
+const MAX_PROGRAM_SIZE: usize = 4096 - PROGRAM_START_ADDRESS as usize;
+
+/// Classic mnemonics, a line starting with one of these is parsed as a
+/// single classic statement
+const MNEMONICS: [&str; 22] = [
+    "CLS", "RET", "SYS", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR",
+    "SUBN", "SHL", "RND", "DRW", "SKP", "SKNP", "DB", "DW",
+];
+
+#[derive(Debug, Error)]
+pub enum AsmError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+
+    #[error("line {line}: undefined label '{name}'")]
+    UndefinedLabel { line: usize, name: String },
+
+    #[error("line {line}: value {value:#X} does not fit in {bits} bits")]
+    OutOfRange { line: usize, value: i64, bits: u32 },
+
+    #[error("program is too large ({size} bytes, max {max})")]
+    TooLarge { size: usize, max: usize },
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    /// A whitespace separated Octo word
+    Word(String),
+    /// A whole line in classic mnemonic syntax, comment stripped
+    Classic(String),
+}
+
+/// A 12 bit address operand waiting for its label to be defined
+struct Fixup {
+    pos: usize,
+    name: String,
+    line: usize,
+}
+
+/// Assemble CHIP-8 source into a ROM image loaded at the program start address.
+///
+/// Two syntaxes are accepted and can be mixed freely:
+///
+/// - Classic mnemonics as printed by the disassembler (`LD V0, 0x10`,
+///   `DRW V0, V1, 5`, `name:` labels, `DB`/`DW` data, `;` comments)
+/// - An Octo subset (`: name` labels, `:alias`, `:const`, `:byte`, `:call`,
+///   `v0 := 5`, `i := label`, `sprite v0 v1 5`, `if ... then`,
+///   `if ... begin ... else ... end`, `loop ... while ... again`, `#` comments,
+///   bare numbers as data and bare label names as calls)
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut asm = Assembler {
+        tokens: tokenize(source),
+        ..Default::default()
+    };
+    asm.run()?;
+    asm.finish()
+}
+
+fn tokenize(source: &str) -> Vec<(usize, Token)> {
+    let mut tokens = Vec::new();
+
+    for (i, raw) in source.lines().enumerate() {
+        let line = i + 1;
+        let text = raw.split('#').next().unwrap_or("");
+        let trimmed = text.trim();
+
+        // `;` is Octo's return, but a `;` followed by text is a classic comment
+        if trimmed.starts_with(';') && trimmed.len() > 1 {
+            continue;
+        }
+
+        let mut words = trimmed.split(|c: char| c.is_whitespace() || c == ',' || c == ';');
+        let mut first = words.next().unwrap_or("");
+        if first.len() > 1 && first.ends_with(':') {
+            first = words.find(|w| !w.is_empty()).unwrap_or("");
+        }
+
+        if MNEMONICS.contains(&first.to_ascii_uppercase().as_str()) {
+            let statement = trimmed.split(';').next().unwrap_or("");
+            tokens.push((line, Token::Classic(statement.to_string())));
+        } else {
+            for word in trimmed.split_whitespace() {
+                tokens.push((line, Token::Word(word.to_string())));
+            }
+        }
+    }
+
+    tokens
+}
+
+fn parse_number(word: &str) -> Option<i64> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, word),
+    };
+
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+        .or_else(|| digits.strip_prefix('$'))
+    {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits
+        .strip_prefix("0b")
+        .or_else(|| digits.strip_prefix("0B"))
+    {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+#[derive(Default)]
+struct Assembler {
+    tokens: Vec<(usize, Token)>,
+    cursor: usize,
+    out: Vec<u8>,
+    labels: HashMap<String, u16>,
+    consts: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    fixups: Vec<Fixup>,
+    /// Open `loop`s: start address and the `while` exits to patch
+    loops: Vec<(u16, Vec<usize>)>,
+    /// Open `if ... begin` blocks: position of the jump to patch
+    branches: Vec<usize>,
+}
+
+impl Assembler {
+    fn pc(&self) -> u16 {
+        PROGRAM_START_ADDRESS + self.out.len() as u16
+    }
+
+    /// Append bytes, failing as soon as the program outgrows memory so that
+    /// addresses stay within 12 bits
+    fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), AsmError> {
+        let size = self.out.len() + bytes.len();
+        if size > MAX_PROGRAM_SIZE {
+            return Err(AsmError::TooLarge {
+                size,
+                max: MAX_PROGRAM_SIZE,
+            });
+        }
+        self.out.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), AsmError> {
+        self.push_bytes(&[byte])
+    }
+
+    fn emit(&mut self, opcode: u16) -> Result<(), AsmError> {
+        self.push_bytes(&opcode.to_be_bytes())
+    }
+
+    fn syntax<T>(line: usize, message: impl Into<String>) -> Result<T, AsmError> {
+        Err(AsmError::Syntax {
+            line,
+            message: message.into(),
+        })
+    }
+
+    fn next_word(&mut self, line: usize, what: &str) -> Result<String, AsmError> {
+        match self.tokens.get(self.cursor) {
+            Some((_, Token::Word(word))) => {
+                self.cursor += 1;
+                Ok(word.clone())
+            }
+            _ => Self::syntax(line, format!("expected {}", what)),
+        }
+    }
+
+    fn define_label(&mut self, name: &str, line: usize) -> Result<(), AsmError> {
+        if self.labels.insert(name.to_string(), self.pc()).is_some() {
+            return Self::syntax(line, format!("label '{}' defined twice", name));
+        }
+        Ok(())
+    }
+
+    fn register(&self, word: &str) -> Option<u8> {
+        if let Some(&reg) = self.aliases.get(word) {
+            return Some(reg);
+        }
+        let digit = word.strip_prefix('v').or_else(|| word.strip_prefix('V'))?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn expect_register(&self, word: &str, line: usize) -> Result<u16, AsmError> {
+        match self.register(word) {
+            Some(reg) => Ok(reg as u16),
+            None => Self::syntax(line, format!("expected a register, found '{}'", word)),
+        }
+    }
+
+    fn value(&self, word: &str) -> Option<i64> {
+        parse_number(word).or_else(|| self.consts.get(word).copied())
+    }
+
+    fn checked(&self, word: &str, line: usize, bits: u32, signed: bool) -> Result<u16, AsmError> {
+        let Some(value) = self.value(word) else {
+            return Self::syntax(line, format!("expected a number, found '{}'", word));
+        };
+        let min = if signed { -(1 << (bits - 1)) } else { 0 };
+        if value < min || value >= 1 << bits {
+            return Err(AsmError::OutOfRange { line, value, bits });
+        }
+        Ok((value & ((1 << bits) - 1)) as u16)
+    }
+
+    fn byte(&self, word: &str, line: usize) -> Result<u16, AsmError> {
+        self.checked(word, line, 8, true)
+    }
+
+    fn nibble(&self, word: &str, line: usize) -> Result<u16, AsmError> {
+        self.checked(word, line, 4, false)
+    }
+
+    /// Emit `prefix | addr`, resolving `target` later if it is a forward label
+    fn emit_address(&mut self, prefix: u16, target: &str, line: usize) -> Result<(), AsmError> {
+        let addr = if let Some(&addr) = self.labels.get(target) {
+            addr
+        } else if self.value(target).is_some() {
+            self.checked(target, line, 12, false)?
+        } else {
+            self.fixups.push(Fixup {
+                pos: self.out.len(),
+                name: target.to_string(),
+                line,
+            });
+            0
+        };
+        self.emit(prefix | addr)?;
+        Ok(())
+    }
+
+    fn patch(&mut self, pos: usize, addr: u16) {
+        self.out[pos] = (self.out[pos] & 0xF0) | (addr >> 8) as u8;
+        self.out[pos + 1] = addr as u8;
+    }
+
+    fn run(&mut self) -> Result<(), AsmError> {
+        while let Some((line, token)) = self.tokens.get(self.cursor).cloned() {
+            self.cursor += 1;
+            match token {
+                Token::Classic(text) => self.classic(&text, line)?,
+                Token::Word(word) => self.octo(&word, line)?,
+            }
+        }
+
+        if !self.loops.is_empty() {
+            return Self::syntax(self.tokens.len(), "'loop' without matching 'again'");
+        }
+        if !self.branches.is_empty() {
+            return Self::syntax(self.tokens.len(), "'begin' without matching 'end'");
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, AsmError> {
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&addr) = self.labels.get(&fixup.name) else {
+                return Err(AsmError::UndefinedLabel {
+                    line: fixup.line,
+                    name: fixup.name,
+                });
+            };
+            self.patch(fixup.pos, addr);
+        }
+        Ok(self.out)
+    }
+
+    /// Parse a condition, returning the opcodes that skip the next
+    /// instruction when it holds and when it does not
+    fn condition(&mut self, line: usize) -> Result<(u16, u16), AsmError> {
+        let lhs = self.next_word(line, "a register")?;
+        let x = self.expect_register(&lhs, line)? << 8;
+        let op = self.next_word(line, "a comparison")?;
+
+        match op.as_str() {
+            "key" => return Ok((0xE09E | x, 0xE0A1 | x)),
+            "-key" => return Ok((0xE0A1 | x, 0xE09E | x)),
+            _ => {}
+        }
+
+        let rhs = self.next_word(line, "a value")?;
+        let (skip_if_equal, skip_if_not_equal) = match self.register(&rhs) {
+            Some(y) => (0x5000 | x | (y as u16) << 4, 0x9000 | x | (y as u16) << 4),
+            None => {
+                let byte = self.byte(&rhs, line)?;
+                (0x3000 | x | byte, 0x4000 | x | byte)
+            }
+        };
+
+        match op.as_str() {
+            "==" => Ok((skip_if_equal, skip_if_not_equal)),
+            "!=" => Ok((skip_if_not_equal, skip_if_equal)),
+            _ => Self::syntax(line, format!("unsupported comparison '{}'", op)),
+        }
+    }
+
+    fn octo(&mut self, word: &str, line: usize) -> Result<(), AsmError> {
+        match word {
+            ":" => {
+                let name = self.next_word(line, "a label name")?;
+                self.define_label(&name, line)?;
+            }
+            ":alias" => {
+                let name = self.next_word(line, "an alias name")?;
+                let reg = self.next_word(line, "a register")?;
+                let reg = self.expect_register(&reg, line)? as u8;
+                self.aliases.insert(name, reg);
+            }
+            ":const" => {
+                let name = self.next_word(line, "a constant name")?;
+                let value = self.next_word(line, "a value")?;
+                let Some(value) = self.value(&value) else {
+                    return Self::syntax(line, format!("expected a number, found '{}'", value));
+                };
+                self.consts.insert(name, value);
+            }
+            ":byte" => {
+                let value = self.next_word(line, "a value")?;
+                let byte = self.byte(&value, line)?;
+                self.push(byte as u8)?;
+            }
+            ":call" => {
+                let target = self.next_word(line, "a call target")?;
+                self.emit_address(0x2000, &target, line)?;
+            }
+            "clear" => self.emit(0x00E0)?,
+            "return" | ";" => self.emit(0x00EE)?,
+            "jump" => {
+                let target = self.next_word(line, "a jump target")?;
+                self.emit_address(0x1000, &target, line)?;
+            }
+            "jump0" => {
+                let target = self.next_word(line, "a jump target")?;
+                self.emit_address(0xB000, &target, line)?;
+            }
+            "sprite" => {
+                let x = self.next_word(line, "a register")?;
+                let y = self.next_word(line, "a register")?;
+                let n = self.next_word(line, "a height")?;
+                let opcode = 0xD000
+                    | self.expect_register(&x, line)? << 8
+                    | self.expect_register(&y, line)? << 4
+                    | self.nibble(&n, line)?;
+                self.emit(opcode)?;
+            }
+            "bcd" | "save" | "load" => {
+                let reg = self.next_word(line, "a register")?;
+                let x = self.expect_register(&reg, line)? << 8;
+                let suffix = match word {
+                    "bcd" => 0x33,
+                    "save" => 0x55,
+                    _ => 0x65,
+                };
+                self.emit(0xF000 | x | suffix)?;
+            }
+            "loop" => self.loops.push((self.pc(), Vec::new())),
+            "while" => {
+                let (when_true, _) = self.condition(line)?;
+                self.emit(when_true)?;
+                let pos = self.out.len();
+                self.emit(0x1000)?;
+                match self.loops.last_mut() {
+                    Some((_, exits)) => exits.push(pos),
+                    None => return Self::syntax(line, "'while' outside of a loop"),
+                }
+            }
+            "again" => {
+                let Some((start, exits)) = self.loops.pop() else {
+                    return Self::syntax(line, "'again' without 'loop'");
+                };
+                self.emit(0x1000 | start)?;
+                let end = self.pc();
+                for pos in exits {
+                    self.patch(pos, end);
+                }
+            }
+            "if" => {
+                let (when_true, when_false) = self.condition(line)?;
+                match self.next_word(line, "'then' or 'begin'")?.as_str() {
+                    "then" => self.emit(when_false)?,
+                    "begin" => {
+                        self.emit(when_true)?;
+                        self.branches.push(self.out.len());
+                        self.emit(0x1000)?;
+                    }
+                    other => {
+                        return Self::syntax(
+                            line,
+                            format!("expected 'then' or 'begin', found '{}'", other),
+                        )
+                    }
+                }
+            }
+            "else" => {
+                let Some(pos) = self.branches.pop() else {
+                    return Self::syntax(line, "'else' without 'begin'");
+                };
+                self.branches.push(self.out.len());
+                self.emit(0x1000)?;
+                let pc = self.pc();
+                self.patch(pos, pc);
+            }
+            "end" => {
+                let Some(pos) = self.branches.pop() else {
+                    return Self::syntax(line, "'end' without 'begin'");
+                };
+                let pc = self.pc();
+                self.patch(pos, pc);
+            }
+            "i" => {
+                let op = self.next_word(line, "':=' or '+='")?;
+                let rhs = self.next_word(line, "a value")?;
+                match op.as_str() {
+                    ":=" if rhs == "hex" => {
+                        let reg = self.next_word(line, "a register")?;
+                        let x = self.expect_register(&reg, line)? << 8;
+                        self.emit(0xF029 | x)?;
+                    }
+                    ":=" => self.emit_address(0xA000, &rhs, line)?,
+                    "+=" => {
+                        let x = self.expect_register(&rhs, line)? << 8;
+                        self.emit(0xF01E | x)?;
+                    }
+                    _ => return Self::syntax(line, format!("unsupported operator 'i {}'", op)),
+                }
+            }
+            "delay" | "buzzer" => {
+                let op = self.next_word(line, "':='")?;
+                if op != ":=" {
+                    return Self::syntax(line, format!("expected ':=', found '{}'", op));
+                }
+                let reg = self.next_word(line, "a register")?;
+                let x = self.expect_register(&reg, line)? << 8;
+                self.emit(if word == "delay" {
+                    0xF015 | x
+                } else {
+                    0xF018 | x
+                })?;
+            }
+            _ if word.len() > 1 && word.ends_with(':') => {
+                self.define_label(&word[..word.len() - 1], line)?;
+            }
+            _ if self.register(word).is_some() => self.assignment(word, line)?,
+            _ if self.value(word).is_some() => {
+                let byte = self.byte(word, line)?;
+                self.push(byte as u8)?;
+            }
+            // A bare name calls the subroutine with that label
+            _ => self.emit_address(0x2000, word, line)?,
+        }
+        Ok(())
+    }
+
+    fn assignment(&mut self, lhs: &str, line: usize) -> Result<(), AsmError> {
+        let x = self.expect_register(lhs, line)? << 8;
+        let op = self.next_word(line, "an operator")?;
+        let rhs = self.next_word(line, "a value")?;
+        let y = self.register(&rhs).map(|y| (y as u16) << 4);
+
+        let opcode = match (op.as_str(), y) {
+            (":=", Some(y)) => 0x8000 | x | y,
+            (":=", None) => match rhs.as_str() {
+                "random" => {
+                    let mask = self.next_word(line, "a mask")?;
+                    0xC000 | x | self.byte(&mask, line)?
+                }
+                "delay" => 0xF007 | x,
+                "key" => 0xF00A | x,
+                _ => 0x6000 | x | self.byte(&rhs, line)?,
+            },
+            ("+=", Some(y)) => 0x8004 | x | y,
+            ("+=", None) => 0x7000 | x | self.byte(&rhs, line)?,
+            ("-=", Some(y)) => 0x8005 | x | y,
+            ("-=", None) => {
+                let byte = self.byte(&rhs, line)?;
+                0x7000 | x | (byte as u8).wrapping_neg() as u16
+            }
+            ("=-", Some(y)) => 0x8007 | x | y,
+            ("|=", Some(y)) => 0x8001 | x | y,
+            ("&=", Some(y)) => 0x8002 | x | y,
+            ("^=", Some(y)) => 0x8003 | x | y,
+            (">>=", Some(y)) => 0x8006 | x | y,
+            ("<<=", Some(y)) => 0x800E | x | y,
+            _ => {
+                return Self::syntax(
+                    line,
+                    format!("unsupported operation '{} {} {}'", lhs, op, rhs),
+                )
+            }
+        };
+        self.emit(opcode)?;
+        Ok(())
+    }
+
+    fn classic(&mut self, text: &str, line: usize) -> Result<(), AsmError> {
+        let mut rest = text.trim();
+
+        // Leading `name:` labels
+        while let Some((head, tail)) = rest.split_once(char::is_whitespace) {
+            match head.strip_suffix(':') {
+                Some(name) if !name.is_empty() => {
+                    self.define_label(name, line)?;
+                    rest = tail.trim_start();
+                }
+                _ => break,
+            }
+        }
+
+        let (mnemonic, operands) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let ops: Vec<&str> = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|op| !op.is_empty())
+            .collect();
+        let upper: Vec<String> = ops.iter().map(|op| op.to_ascii_uppercase()).collect();
+        let upper: Vec<&str> = upper.iter().map(String::as_str).collect();
+
+        let reg = |i: usize| self.expect_register(ops[i], line);
+
+        let opcode = match (mnemonic.as_str(), upper.as_slice()) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("SYS", [_]) => return self.emit_address(0x0000, ops[0], line),
+            ("JP", [_]) => return self.emit_address(0x1000, ops[0], line),
+            ("JP", ["V0", _]) => return self.emit_address(0xB000, ops[1], line),
+            ("CALL", [_]) => return self.emit_address(0x2000, ops[0], line),
+            ("SE" | "SNE", [_, _]) => {
+                let x = reg(0)? << 8;
+                let equal = mnemonic == "SE";
+                match self.register(ops[1]) {
+                    Some(y) => (if equal { 0x5000 } else { 0x9000 }) | x | (y as u16) << 4,
+                    None => (if equal { 0x3000 } else { 0x4000 }) | x | self.byte(ops[1], line)?,
+                }
+            }
+            ("LD", ["I", _]) => return self.emit_address(0xA000, ops[1], line),
+            ("LD", ["DT", _]) => 0xF015 | reg(1)? << 8,
+            ("LD", ["ST", _]) => 0xF018 | reg(1)? << 8,
+            ("LD", ["F", _]) => 0xF029 | reg(1)? << 8,
+            ("LD", ["B", _]) => 0xF033 | reg(1)? << 8,
+            ("LD", ["[I]", _]) => 0xF055 | reg(1)? << 8,
+            ("LD", [_, "DT"]) => 0xF007 | reg(0)? << 8,
+            ("LD", [_, "K"]) => 0xF00A | reg(0)? << 8,
+            ("LD", [_, "[I]"]) => 0xF065 | reg(0)? << 8,
+            ("LD", [_, _]) => match self.register(ops[1]) {
+                Some(y) => 0x8000 | reg(0)? << 8 | (y as u16) << 4,
+                None => 0x6000 | reg(0)? << 8 | self.byte(ops[1], line)?,
+            },
+            ("ADD", ["I", _]) => 0xF01E | reg(1)? << 8,
+            ("ADD", [_, _]) => match self.register(ops[1]) {
+                Some(y) => 0x8004 | reg(0)? << 8 | (y as u16) << 4,
+                None => 0x7000 | reg(0)? << 8 | self.byte(ops[1], line)?,
+            },
+            ("OR" | "AND" | "XOR" | "SUB" | "SUBN", [_, _]) => {
+                let n = match mnemonic.as_str() {
+                    "OR" => 0x1,
+                    "AND" => 0x2,
+                    "XOR" => 0x3,
+                    "SUB" => 0x5,
+                    _ => 0x7,
+                };
+                0x8000 | reg(0)? << 8 | reg(1)? << 4 | n
+            }
+            ("SHR" | "SHL", [_] | [_, _]) => {
+                let y = if ops.len() == 2 { reg(1)? } else { 0 };
+                let n = if mnemonic == "SHR" { 0x6 } else { 0xE };
+                0x8000 | reg(0)? << 8 | y << 4 | n
+            }
+            ("RND", [_, _]) => 0xC000 | reg(0)? << 8 | self.byte(ops[1], line)?,
+            ("DRW", [_, _, _]) => {
+                0xD000 | reg(0)? << 8 | reg(1)? << 4 | self.nibble(ops[2], line)?
+            }
+            ("SKP", [_]) => 0xE09E | reg(0)? << 8,
+            ("SKNP", [_]) => 0xE0A1 | reg(0)? << 8,
+            ("DB", _) => {
+                for op in &ops {
+                    let byte = self.byte(op, line)?;
+                    self.push(byte as u8)?;
+                }
+                return Ok(());
+            }
+            ("DW", _) => {
+                for op in &ops {
+                    let word = self.checked(op, line, 16, false)?;
+                    self.emit(word)?;
+                }
+                return Ok(());
+            }
+            _ => {
+                return Self::syntax(
+                    line,
+                    format!("invalid operands for {}: '{}'", mnemonic, operands.trim()),
+                )
+            }
+        };
+
+        self.emit(opcode)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulators::chip8::disasm;
+
+    fn words(rom: &[u8]) -> Vec<u16> {
+        rom.chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+            .collect()
+    }
+
+    #[test]
+    fn listings_assemble_back_to_the_rom() {
+        let rom: Vec<u8> = [
+            0x00E0u16, // 200: CLS
+            0x6A02,    // 202: LD VA, 0x02
+            0x6B0C,    // 204: LD VB, 0x0C
+            0xA216,    // 206: LD I, sprite
+            0x2210,    // 208: CALL draw
+            0x7A01,    // 20A: ADD VA, 0x01
+            0x3A10,    // 20C: SE VA, 0x10
+            0x1208,    // 20E: JP 208
+            0xDAB5,    // 210: draw: DRW VA, VB, 5
+            0x8AB4,    // 212: ADD VA, VB
+            0x00EE,    // 214: RET
+            0xF090,    // 216: sprite data
+            0x9090,
+        ]
+        .iter()
+        .flat_map(|word| word.to_be_bytes())
+        .chain([0xF0])
+        .collect();
+
+        let listing = disasm::rom_listing(&rom);
+        assert_eq!(assemble(&listing).unwrap(), rom, "{}", listing);
+    }
+
+    #[test]
+    fn classic_mnemonics() {
+        let source = "
+            start: LD V0, 0x10   ; comment
+                   LD I, data
+                   DRW V0, V1, 5
+                   SKNP V2
+                   JP start
+            data:  DB 0xFF, 0x81
+                   DW 0x1234
+        ";
+        assert_eq!(
+            assemble(source).unwrap(),
+            [0x60, 0x10, 0xA2, 0x0A, 0xD0, 0x15, 0xE2, 0xA1, 0x12, 0x00, 0xFF, 0x81, 0x12, 0x34]
+        );
+    }
+
+    #[test]
+    fn octo_syntax() {
+        let source = "
+            :const SPEED 3
+            :alias x v1
+            : main
+                x := 0
+                i := hex x
+                loop
+                    x += SPEED
+                    if x == 9 then x := 1
+                    while x != 12
+                again
+                if vf key begin
+                    draw
+                else
+                    v2 := random 0xF
+                end
+                delay := x
+                jump main
+            : draw
+                sprite x x 1
+                return
+        ";
+        assert_eq!(
+            words(&assemble(source).unwrap()),
+            [
+                0x6100, // x := 0
+                0xF129, // i := hex x
+                0x7103, // loop: x += SPEED
+                0x4109, // if x == 9 then
+                0x6101, //   x := 1
+                0x410C, // while x != 12
+                0x1210, //   (exit)
+                0x1204, // again
+                0xEF9E, // if vf key begin
+                0x1218, //   (to else)
+                0x221E, // draw
+                0x121A, // else (to end)
+                0xC20F, // v2 := random 0xF
+                0xF115, // end: delay := x
+                0x1200, // jump main
+                0xD111, // draw: sprite x x 1
+                0x00EE, // return
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert!(matches!(
+            assemble("LD V0, 0x10\nJP nowhere"),
+            Err(AsmError::UndefinedLabel { line: 2, .. })
+        ));
+        assert!(matches!(
+            assemble("v0 := 256"),
+            Err(AsmError::OutOfRange {
+                line: 1,
+                bits: 8,
+                ..
+            })
+        ));
+        assert!(matches!(
+            assemble("loop v0 += 1"),
+            Err(AsmError::Syntax { .. })
+        ));
+    }
+
+    #[test]
+    fn oversized_programs_fail_before_addresses_overflow() {
+        // A label past the end of memory must not wrap around
+        let source = format!("{}\n: end jump end", "0 ".repeat(MAX_PROGRAM_SIZE));
+        assert!(matches!(
+            assemble(&source),
+            Err(AsmError::TooLarge {
+                size,
+                max: MAX_PROGRAM_SIZE
+            }) if size == MAX_PROGRAM_SIZE + 2
+        ));
+        assert_eq!(
+            assemble(&"0 ".repeat(MAX_PROGRAM_SIZE)).unwrap().len(),
+            MAX_PROGRAM_SIZE
+        );
+    }
+}
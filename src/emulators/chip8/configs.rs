@@ -2,3 +2,14 @@ pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
 pub const PROGRAM_START_ADDRESS: u16 = 0x200;
 pub const FONTSET_START_ADDRESS: u16 = 0x50;
+/// Delay and sound timers count down at 60 Hz
+pub const TIMER_PERIOD: std::time::Duration = std::time::Duration::from_nanos(1_000_000_000 / 60);
+/// Machine cycles per second the COSMAC VIP interpreter gets to run, after
+/// the display DMA takes its share of every frame
+pub const VIP_CYCLES_PER_SECOND: u32 = 3668 * 60;
+/// Instructions per second when not emulating VIP timing
+pub const DEFAULT_INSTRUCTION_HZ: u32 = 700;
+/// Initial state of the random number generator
+pub const DEFAULT_SEED: u8 = 31;
+/// Background, plane 1, plane 2 and both planes, as ARGB8888: white on black
+pub const DEFAULT_PALETTE: [u32; 4] = [0xFF000000, 0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555];
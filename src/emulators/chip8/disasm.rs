@@ -0,0 +1,239 @@
+use super::analysis::{ByteKind, CodeMap};
+use super::configs::PROGRAM_START_ADDRESS;
+
+/// Decode a single opcode into its mnemonic and a human readable comment.
+///
+/// Mnemonics use the classic syntax accepted by the assembler, so a listing
+/// can be assembled back into the original ROM.
+pub fn decode(opcode: u16) -> (String, String) {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let kk = opcode & 0x00FF;
+    let nnn = opcode & 0x0FFF;
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => ("CLS".to_string(), "Clear display".to_string()),
+            0x00EE => ("RET".to_string(), "Return from subroutine".to_string()),
+            _ => (
+                format!("SYS 0x{:03X}", nnn),
+                format!("Call system routine SYS {:03X}", nnn),
+            ),
+        },
+        0x1000 => (
+            format!("JP 0x{:03X}", nnn),
+            format!("Jump to address {:03X}", nnn),
+        ),
+        0x2000 => (
+            format!("CALL 0x{:03X}", nnn),
+            format!("Call subroutine at {:03X}", nnn),
+        ),
+        0x3000 => (
+            format!("SE V{:X}, 0x{:02X}", x, kk),
+            format!("Skip if V{:X} == {:02X}", x, kk),
+        ),
+        0x4000 => (
+            format!("SNE V{:X}, 0x{:02X}", x, kk),
+            format!("Skip if V{:X} != {:02X}", x, kk),
+        ),
+        0x5000 => (
+            format!("SE V{:X}, V{:X}", x, y),
+            format!("Skip if V{:X} == V{:X}", x, y),
+        ),
+        0x6000 => (
+            format!("LD V{:X}, 0x{:02X}", x, kk),
+            format!("Load {:02X} into V{:X}", kk, x),
+        ),
+        0x7000 => (
+            format!("ADD V{:X}, 0x{:02X}", x, kk),
+            format!("Add {:02X} to V{:X}", kk, x),
+        ),
+        0x8000 => match n {
+            0x0 => (
+                format!("LD V{:X}, V{:X}", x, y),
+                format!("V{:X} = V{:X}", x, y),
+            ),
+            0x1 => (
+                format!("OR V{:X}, V{:X}", x, y),
+                format!("V{:X} |= V{:X}", x, y),
+            ),
+            0x2 => (
+                format!("AND V{:X}, V{:X}", x, y),
+                format!("V{:X} &= V{:X}", x, y),
+            ),
+            0x3 => (
+                format!("XOR V{:X}, V{:X}", x, y),
+                format!("V{:X} ^= V{:X}", x, y),
+            ),
+            0x4 => (
+                format!("ADD V{:X}, V{:X}", x, y),
+                format!("V{:X} += V{:X}, VF = carry", x, y),
+            ),
+            0x5 => (
+                format!("SUB V{:X}, V{:X}", x, y),
+                format!("V{:X} -= V{:X}, VF = borrow", x, y),
+            ),
+            0x6 => (
+                format!("SHR V{:X}, V{:X}", x, y),
+                format!("V{:X} >>= 1, VF = carry", x),
+            ),
+            0x7 => (
+                format!("SUBN V{:X}, V{:X}", x, y),
+                format!("V{:X} = V{:X} - V{:X}, VF = borrow", x, y, x),
+            ),
+            0xE => (
+                format!("SHL V{:X}, V{:X}", x, y),
+                format!("V{:X} <<= 1, VF = carry", x),
+            ),
+            _ => (
+                format!("8{:X}{:X}{:X}", x, y, n),
+                "Unknown 8xxx instruction".to_string(),
+            ),
+        },
+        0x9000 => (
+            format!("SNE V{:X}, V{:X}", x, y),
+            format!("Skip if V{:X} != V{:X}", x, y),
+        ),
+        0xA000 => (format!("LD I, 0x{:03X}", nnn), format!("I = {:03X}", nnn)),
+        0xB000 => (
+            format!("JP V0, 0x{:03X}", nnn),
+            format!("Jump to V0 + {:03X}", nnn),
+        ),
+        0xC000 => (
+            format!("RND V{:X}, 0x{:02X}", x, kk),
+            format!("V{:X} = random() & {:02X}", x, kk),
+        ),
+        0xD000 => (
+            format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            format!("Draw sprite at (V{:X}, V{:X}) height {:X}", x, y, n),
+        ),
+        0xE000 => match kk {
+            0x9E => (
+                format!("SKP V{:X}", x),
+                format!("Skip if key V{:X} pressed", x),
+            ),
+            0xA1 => (
+                format!("SKNP V{:X}", x),
+                format!("Skip if key V{:X} not pressed", x),
+            ),
+            _ => (
+                format!("E{:X}{:02X}", x, kk),
+                "Unknown Exxx instruction".to_string(),
+            ),
+        },
+        0xF000 => match kk {
+            0x07 => (
+                format!("LD V{:X}, DT", x),
+                format!("V{:X} = delay timer", x),
+            ),
+            0x0A => (
+                format!("LD V{:X}, K", x),
+                format!("Wait for key, store in V{:X}", x),
+            ),
+            0x15 => (
+                format!("LD DT, V{:X}", x),
+                format!("Delay timer = V{:X}", x),
+            ),
+            0x18 => (
+                format!("LD ST, V{:X}", x),
+                format!("Sound timer = V{:X}", x),
+            ),
+            0x1E => (format!("ADD I, V{:X}", x), format!("I += V{:X}", x)),
+            0x29 => (
+                format!("LD F, V{:X}", x),
+                format!("I = sprite address for digit V{:X}", x),
+            ),
+            0x33 => (
+                format!("LD B, V{:X}", x),
+                format!("Store BCD of V{:X} at [I]", x),
+            ),
+            0x55 => (
+                format!("LD [I], V{:X}", x),
+                format!("Store V0-V{:X} at [I]", x),
+            ),
+            0x65 => (
+                format!("LD V{:X}, [I]", x),
+                format!("Load V0-V{:X} from [I]", x),
+            ),
+            _ => (
+                format!("F{:X}{:02X}", x, kk),
+                "Unknown Fxxx instruction".to_string(),
+            ),
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Like [`decode`], but refers to addresses by their label when one exists
+pub fn decode_symbolic(opcode: u16, map: &CodeMap) -> (String, String) {
+    let (mnemonic, comment) = decode(opcode);
+    let nnn = opcode & 0x0FFF;
+
+    let label = match opcode & 0xF000 {
+        0x1000 | 0x2000 | 0xA000 | 0xB000 if map.is_line_start(nnn) => map.label(nnn),
+        _ => None,
+    };
+
+    match label {
+        Some(label) => (mnemonic.replace(&format!("0x{:03X}", nnn), &label), comment),
+        None => (mnemonic, comment),
+    }
+}
+
+/// Render a byte as an 8 pixel wide sprite row, e.g. `0x3C` -> `..####..`
+pub fn sprite_row(byte: u8) -> String {
+    (0..8)
+        .map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' })
+        .collect()
+}
+
+/// Produce a full listing of the analyzed region, separating code from data.
+///
+/// Code is printed as mnemonics and data bytes as `DB` lines with their
+/// bitmap, so sprites are recognizable at a glance.
+pub fn listing(memory: &[u8], map: &CodeMap) -> String {
+    let mut out = String::new();
+
+    for line in map.lines() {
+        if let Some(label) = map.label(line.addr) {
+            out.push_str(&format!("{}:\n", label));
+        }
+
+        match line.kind {
+            ByteKind::Code => {
+                let opcode = (memory[line.addr as usize] as u16) << 8
+                    | memory[line.addr as usize + 1] as u16;
+                let (mnemonic, comment) = decode_symbolic(opcode, map);
+                out.push_str(&format!(
+                    "    {:<20}; {:03X}: {:04X}  {}\n",
+                    mnemonic, line.addr, opcode, comment
+                ));
+            }
+            _ => {
+                let byte = memory[line.addr as usize];
+                out.push_str(&format!(
+                    "    {:<20}; {:03X}: {:02X}    {}\n",
+                    format!("DB 0x{:02X}", byte),
+                    line.addr,
+                    byte,
+                    sprite_row(byte)
+                ));
+            }
+        }
+    }
+
+    out
+}
+
+/// Analyze a raw ROM image as if loaded at the program start address and
+/// produce its listing
+pub fn rom_listing(rom: &[u8]) -> String {
+    let mut memory = [0u8; 4096];
+    let start = PROGRAM_START_ADDRESS as usize;
+    let len = rom.len().min(memory.len() - start);
+    memory[start..start + len].copy_from_slice(&rom[..len]);
+
+    let map = CodeMap::analyze(&memory, PROGRAM_START_ADDRESS, (start + len) as u16);
+    listing(&memory, &map)
+}
@@ -1,10 +1,19 @@
-mod configs;
+pub mod analysis;
+pub mod asm;
+pub mod configs;
+pub mod disasm;
 mod rand;
-
-use super::{EmuError, Emulator};
-use configs::{FONTSET_START_ADDRESS, HEIGHT, PROGRAM_START_ADDRESS, WIDTH};
+#[cfg(test)]
+mod tests;
+
+use super::{EmuError, Emulator, ExecutionPolicy};
+use configs::{
+    DEFAULT_PALETTE, DEFAULT_SEED, FONTSET_START_ADDRESS, HEIGHT, PROGRAM_START_ADDRESS,
+    TIMER_PERIOD, WIDTH,
+};
 use rand::Lcg;
 use raplay::{source::Sine, Sink};
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
@@ -30,6 +39,28 @@ const FONT_SET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+/// Details of the most recent DXYN, used by the display overlay
+#[derive(Debug, Clone, Default)]
+pub struct SpriteDraw {
+    pub x: usize,
+    pub y: usize,
+    pub height: usize,
+    /// Screen pixels that were erased by the sprite, i.e. the ones that set VF
+    pub collisions: Vec<(usize, usize)>,
+}
+
+/// Number of CALL/RET events kept for stack fault diagnostics
+pub const CALL_TRACE_LEN: usize = 32;
+
+/// A subroutine call or return, recorded so stack faults can be traced back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackEvent {
+    /// `CALL target` executed at `from`
+    Call { from: u16, target: u16 },
+    /// `RET` executed at `from`, resuming at `to`
+    Return { from: u16, to: u16 },
+}
+
 /// CHIP-8 specific metadata
 #[derive(Debug, Clone)]
 pub struct Chip8Metadata {
@@ -42,6 +73,11 @@ pub struct Chip8Metadata {
     pub sound_timer: u8,
     pub current_opcode: u16,
     pub memory: [u8; 4096],
+    pub last_draw: Option<SpriteDraw>,
+    /// Most recent CALL/RET events, oldest first
+    pub call_trace: Vec<StackEvent>,
+    /// Questionable operations that were wrapped or ignored under the lenient policy
+    pub lenient_fixups: u64,
 }
 
 /// Implementation of the CHIP-8 emulator
@@ -59,9 +95,23 @@ pub struct Chip8Emulator {
     framebuffer: [u32; WIDTH * HEIGHT],
     current_opcode: u16,
     lcg: Lcg,
-    audio: Sink,
+    seed: u8,
+    /// Created on the first beep, so muted and headless runs never open an audio device
+    audio: Option<Sink>,
     is_mute: bool,
+    volume: f32,
     is_key_pressed: bool,
+    last_draw: Option<SpriteDraw>,
+    call_trace: VecDeque<StackEvent>,
+    policy: ExecutionPolicy,
+    lenient_fixups: u64,
+    last_cycles: u32,
+    timer_accumulator: Duration,
+    display_wait: bool,
+    vblank_wait: bool,
+    /// Rows of `buffer` not yet converted into `framebuffer`
+    dirty_rows: [bool; HEIGHT],
+    palette: [u32; 4],
 }
 
 impl Chip8Emulator {
@@ -71,11 +121,6 @@ impl Chip8Emulator {
             ..(FONTSET_START_ADDRESS + FONT_SET.len() as u16) as usize]
             .copy_from_slice(&FONT_SET);
 
-        // Try to initialize audio, but don't fail if it's not available
-        let mut sink = Sink::default();
-        let src = Sine::new(440.0);
-        let _ = sink.load(Box::new(src), false); // Ignore errors
-
         Self {
             registers: [0; 16],
             memory,
@@ -89,25 +134,146 @@ impl Chip8Emulator {
             current_opcode: 0,
             buffer: [false; WIDTH * HEIGHT],
             framebuffer: [0; WIDTH * HEIGHT],
-            lcg: Lcg::new(75, 1, 31),
-            audio: sink,
+            lcg: Lcg::new(75, 1, DEFAULT_SEED),
+            seed: DEFAULT_SEED,
+            audio: None,
             is_mute: false,
+            volume: 1.0,
             is_key_pressed: false,
+            last_draw: None,
+            call_trace: VecDeque::with_capacity(CALL_TRACE_LEN),
+            policy: ExecutionPolicy::default(),
+            lenient_fixups: 0,
+            last_cycles: 0,
+            timer_accumulator: Duration::ZERO,
+            display_wait: false,
+            vblank_wait: false,
+            dirty_rows: [true; HEIGHT],
+            palette: DEFAULT_PALETTE,
         }
     }
 
-    pub fn set_mute(&mut self, mute: bool) {
-        self.is_mute = mute;
+    fn record_stack_event(&mut self, event: StackEvent) {
+        if self.call_trace.len() == CALL_TRACE_LEN {
+            self.call_trace.pop_front();
+        }
+        self.call_trace.push_back(event);
+    }
+
+    fn audio(&mut self) -> &mut Sink {
+        let volume = self.volume;
+        self.audio.get_or_insert_with(|| {
+            // Try to initialize audio, but don't fail if it's not available
+            let mut sink = Sink::default();
+            let src = Sine::new(440.0);
+            let _ = sink.load(Box::new(src), false); // Ignore errors
+            let _ = sink.volume(volume);
+            sink
+        })
+    }
+
+    /// Approximate machine cycles an instruction takes in the COSMAC VIP
+    /// interpreter, including fetch and decode
+    fn vip_cycles(&self, opcode: u16) -> u32 {
+        const FETCH: u32 = 68;
+        let x = ((opcode & 0x0F00) >> 8) as u32;
+        let n = (opcode & 0x000F) as u32;
+
+        FETCH
+            + match opcode & 0xF000 {
+                0x0000 if opcode == 0x00E0 => 24 + 3078,
+                0x0000 => 10,
+                0x1000 | 0xA000 => 12,
+                0x2000 => 26,
+                0x3000 | 0x4000 | 0x7000 => 10,
+                0x5000 | 0x9000 => 14,
+                0x6000 => 6,
+                0x8000 => 44,
+                0xB000 => 22,
+                0xC000 => 36,
+                0xD000 => 22 + 46 * n,
+                0xE000 => 14,
+                _ => match opcode & 0x00FF {
+                    0x1E | 0x29 => 16,
+                    0x33 => 84,
+                    0x55 | 0x65 => 14 + 14 * (x + 1),
+                    _ => 10,
+                },
+            }
     }
 
-    fn update_framebuffer(&mut self) {
-        for (i, &pixel) in self.buffer.iter().enumerate() {
-            self.framebuffer[i] = if pixel {
-                0xFFFFFFFF // white ARGB
-            } else {
-                0xFF000000 // black ARGB
-            };
+    /// Address of the instruction being executed, for error reports
+    fn fault_pc(&self) -> u64 {
+        self.program_counter.wrapping_sub(2) as u64
+    }
+
+    /// Handle a questionable operation according to the policy: error out
+    /// when strict, otherwise count the fixup and let the caller carry on
+    fn fixup(&mut self, error: impl FnOnce() -> EmuError) -> Result<(), EmuError> {
+        match self.policy {
+            ExecutionPolicy::Strict => Err(error()),
+            ExecutionPolicy::Lenient => {
+                self.lenient_fixups += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Make sure `len` bytes starting at I lie in memory. Under the lenient
+    /// policy accesses wrap around, see [`Self::index_address`].
+    fn check_index(&mut self, len: usize) -> Result<(), EmuError> {
+        let last = self.index_register as usize + len.saturating_sub(1);
+        if len > 0 && last >= self.memory.len() {
+            let pc = self.fault_pc();
+            self.fixup(|| EmuError::IndexOutOfBounds {
+                addr: last as u64,
+                pc,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Memory address `offset` bytes past I, wrapped into the address space
+    fn index_address(&self, offset: usize) -> usize {
+        (self.index_register as usize + offset) % self.memory.len()
+    }
+
+    /// Vx as a key or font digit, which only go up to 0xF
+    fn nibble_operand(&mut self, x: usize) -> Result<u8, EmuError> {
+        let value = self.registers[x];
+        if value > 0xF {
+            let pc = self.fault_pc();
+            self.fixup(|| EmuError::ValueOutOfRange {
+                value: value as u64,
+                max: 0xF,
+                pc,
+            })?;
         }
+        Ok(value % 16)
+    }
+
+    /// Convert the rows of `buffer` that changed since the last call into
+    /// ARGB pixels. Returns whether anything was converted.
+    fn update_framebuffer(&mut self) -> bool {
+        let mut changed = false;
+        for row in 0..HEIGHT {
+            if !std::mem::take(&mut self.dirty_rows[row]) {
+                continue;
+            }
+            changed = true;
+            let pixels = row * WIDTH..(row + 1) * WIDTH;
+            for (out, &pixel) in self.framebuffer[pixels.clone()]
+                .iter_mut()
+                .zip(&self.buffer[pixels])
+            {
+                *out = if pixel {
+                    self.palette[1]
+                } else {
+                    self.palette[0]
+                };
+            }
+        }
+        changed
     }
 
     fn parse_opcode(&mut self, opcode: u16) -> Result<(), EmuError> {
@@ -118,21 +284,37 @@ impl Chip8Emulator {
             0x00E0 => {
                 // CLS, clear display
                 self.buffer = [false; WIDTH * HEIGHT];
+                self.dirty_rows = [true; HEIGHT];
             }
             0x00EE => {
                 // RET, return from subroutine
                 let subtracted_stack_pointer = self.stack_pointer.wrapping_sub(1);
 
                 if (0..16).contains(&subtracted_stack_pointer) {
+                    let from = self.program_counter.wrapping_sub(2);
                     self.stack_pointer = self.stack_pointer.wrapping_sub(1);
                     self.program_counter = self.stack[self.stack_pointer as usize];
+                    self.record_stack_event(StackEvent::Return {
+                        from,
+                        to: self.program_counter,
+                    });
                 } else {
                     return Err(EmuError::InvalidStackAccess {
                         sp: subtracted_stack_pointer as u64,
-                        pc: self.program_counter as u64,
+                        pc: self.fault_pc(),
                     });
                 }
             }
+            0x0000..=0x0FFF => {
+                // SYS addr, run a machine code routine on the COSMAC VIP. Only
+                // the interpreter is emulated, so the call is skipped.
+                let pc = self.fault_pc();
+                self.fixup(|| EmuError::InvalidOpcodeUsage {
+                    opcode: opcode as u64,
+                    pc,
+                    hint: " (SYS machine code routines are not supported)",
+                })?;
+            }
             0x1000..=0x1FFF => {
                 // JP addr, target address = opcode & 0x0FFF
                 self.program_counter = opcode & 0x0FFF;
@@ -140,13 +322,18 @@ impl Chip8Emulator {
             0x2000..=0x2FFF => {
                 // CALL addr, target address = opcode & 0x0FFF
                 if (0..16).contains(&self.stack_pointer) {
+                    let from = self.program_counter.wrapping_sub(2);
                     self.stack[self.stack_pointer as usize] = self.program_counter;
                     self.stack_pointer += 1;
                     self.program_counter = opcode & 0x0FFF;
+                    self.record_stack_event(StackEvent::Call {
+                        from,
+                        target: self.program_counter,
+                    });
                 } else {
                     return Err(EmuError::InvalidStackAccess {
                         sp: self.stack_pointer as u64,
-                        pc: self.program_counter as u64,
+                        pc: self.fault_pc(),
                     });
                 }
             }
@@ -167,14 +354,16 @@ impl Chip8Emulator {
             0x5000..=0x5FFF => {
                 // SE Vx, Vy, skip next instruction if Vx == Vy
 
-                // Check if last nibble is 0
-                if opcode & 0x1 != 0 {
+                // Check if last nibble is 0. A malformed encoding, not a
+                // questionable operation, so rejected under either policy
+                if opcode & 0xF != 0 {
                     return Err(EmuError::InvalidOpcodeUsage {
                         opcode: opcode as u64,
-                        pc: self.program_counter as u64,
+                        pc: self.fault_pc(),
                         hint: " (Set last nibble to 0)",
                     });
-                } else if self.registers[x] == self.registers[y] {
+                }
+                if self.registers[x] == self.registers[y] {
                     self.program_counter = self.program_counter.wrapping_add(2);
                 }
             }
@@ -245,7 +434,7 @@ impl Chip8Emulator {
                     _ => {
                         return Err(EmuError::InvalidOpcodeUsage {
                             opcode: opcode as u64,
-                            pc: self.program_counter as u64,
+                            pc: self.fault_pc(),
                             hint: " (Set last nibble to 0, 1, 2, 3, 4, 5, 6, 7 or E)",
                         });
                     }
@@ -254,14 +443,16 @@ impl Chip8Emulator {
             0x9000..=0x9FFF => {
                 // SNE Vx, Vy, skip next instruction if Vx != Vy
 
-                // Check if last nibble is 0
-                if opcode & 0x1 != 0 {
+                // Check if last nibble is 0. A malformed encoding, not a
+                // questionable operation, so rejected under either policy
+                if opcode & 0xF != 0 {
                     return Err(EmuError::InvalidOpcodeUsage {
                         opcode: opcode as u64,
-                        pc: self.program_counter as u64,
+                        pc: self.fault_pc(),
                         hint: " (Set last nibble to 0)",
                     });
-                } else if self.registers[x] != self.registers[y] {
+                }
+                if self.registers[x] != self.registers[y] {
                     self.program_counter = self.program_counter.wrapping_add(2);
                 }
             }
@@ -284,20 +475,20 @@ impl Chip8Emulator {
                 let height = (opcode & 0x000F) as usize;
                 let x_pos = self.registers[x] as usize % WIDTH;
                 let y_pos = self.registers[y] as usize % HEIGHT;
+                self.check_index(height)?;
                 self.registers[0xF] = 0;
+                let mut draw = SpriteDraw {
+                    x: x_pos,
+                    y: y_pos,
+                    height,
+                    collisions: Vec::new(),
+                };
 
                 for row in 0..height {
                     let screen_y = (y_pos + row) % HEIGHT;
+                    self.dirty_rows[screen_y] = true;
 
-                    let index = self.index_register as usize + row;
-                    let sprite_byte = if index < self.memory.len() {
-                        self.memory[index]
-                    } else {
-                        return Err(EmuError::InvalidRegisterIndex {
-                            index,
-                            pc: self.program_counter as u64,
-                        });
-                    };
+                    let sprite_byte = self.memory[self.index_address(row)];
 
                     for col in 0..8 {
                         let screen_x = (x_pos + col) % WIDTH;
@@ -308,42 +499,35 @@ impl Chip8Emulator {
                             let screen_pixel = &mut self.buffer[screen_index];
                             if *screen_pixel {
                                 self.registers[0xF] = 1;
+                                draw.collisions.push((screen_x, screen_y));
                             }
                             *screen_pixel = !*screen_pixel;
                         }
                     }
                 }
+                self.last_draw = Some(draw);
+                self.vblank_wait = self.display_wait;
             }
             0xE000..=0xEFFF => {
                 match opcode & 0x00FF {
                     0x9E => {
                         // SKP Vx, skip next instruction if key Vx is pressed
-                        if self.registers[x] > 15 {
-                            log::warn!("Vx value not in 0..=15 range: {:#06X}", self.registers[x]);
-                        }
-
-                        let x_val = self.registers[x] % 16;
-
-                        if self.input_keys[x_val as usize] {
+                        let key = self.nibble_operand(x)?;
+                        if self.input_keys[key as usize] {
                             self.program_counter = self.program_counter.wrapping_add(2);
                         }
                     }
                     0xA1 => {
                         // SKP Vx, skip next instruction if key Vx is NOT pressed
-                        if self.registers[x] > 15 {
-                            log::warn!("Vx value not in 0..=15 range: {:#06X}", self.registers[x]);
-                        }
-
-                        let x_val = self.registers[x] % 16;
-
-                        if !self.input_keys[x_val as usize] {
+                        let key = self.nibble_operand(x)?;
+                        if !self.input_keys[key as usize] {
                             self.program_counter = self.program_counter.wrapping_add(2);
                         }
                     }
                     _ => {
                         return Err(EmuError::InvalidOpcodeUsage {
                             opcode: opcode as u64,
-                            pc: self.program_counter as u64,
+                            pc: self.fault_pc(),
                             hint: " (For Ex prefix, only 9E and A1 suffix are supported)",
                         });
                     }
@@ -387,64 +571,51 @@ impl Chip8Emulator {
                     }
                     0x29 => {
                         // LD F, Vx, Set I = location of sprite for digit Vx.
-                        if self.registers[x] > 15 {
-                            log::warn!("Vx value not in 0..=15 range: {:#06X}", self.registers[x]);
-                        }
-
-                        let x_val = self.registers[x] % 16;
-                        let index = FONTSET_START_ADDRESS
-                            .wrapping_add((x_val as u16).wrapping_mul(5));
-
-                        if index > 4096 {
-                            log::warn!("Index value crossing 4KiB boundary: {:#06X}", index);
-                        }
-
-                        self.index_register = index % 4096;
+                        let digit = self.nibble_operand(x)?;
+                        self.index_register = FONTSET_START_ADDRESS + digit as u16 * 5;
                     }
                     0x33 => {
                         // LD B, Vx, Store BCD representation of Vx in memory locations I, I+1, and I+2.
+                        self.check_index(3)?;
                         let mut value = self.registers[x];
 
-                        self.memory[self.index_register as usize + 2] = value % 10; // Ones
+                        let ones = self.index_address(2);
+                        self.memory[ones] = value % 10;
                         value /= 10;
 
-                        self.memory[self.index_register as usize + 1] = value % 10; // Tens
+                        let tens = self.index_address(1);
+                        self.memory[tens] = value % 10;
                         value /= 10;
 
-                        self.memory[self.index_register as usize] = value % 10;
-                        // Hundreds
+                        let hundreds = self.index_address(0);
+                        self.memory[hundreds] = value % 10;
                     }
                     0x55 => {
                         // LD [I], Vx, Store registers V0 through Vx in memory starting at location I.
+                        self.check_index(x + 1)?;
                         for index in 0..=x {
-                            self.memory[self.index_register as usize + index] =
-                                self.registers[index];
+                            let addr = self.index_address(index);
+                            self.memory[addr] = self.registers[index];
                         }
-                        self.index_register += 1 + x as u16;
+                        self.index_register = self.index_register.wrapping_add(1 + x as u16);
                     }
                     0x65 => {
                         // LD Vx, [I], Read registers V0 through Vx from memory starting at location I.
+                        self.check_index(x + 1)?;
                         for index in 0..=x {
-                            self.registers[index] =
-                                self.memory[self.index_register as usize + index];
+                            self.registers[index] = self.memory[self.index_address(index)];
                         }
-                        self.index_register += 1 + x as u16;
+                        self.index_register = self.index_register.wrapping_add(1 + x as u16);
                     }
                     _ => {
                         return Err(EmuError::InvalidOpcodeUsage {
                             opcode: opcode as u64,
-                            pc: self.program_counter as u64,
+                            pc: self.fault_pc(),
                             hint: " (For Fx prefix, only 07, 0A, 15, 18, 1E, 29, 33, 55 and 65 suffix are supported)",
                         });
                     }
                 }
             }
-            _ => {
-                return Err(EmuError::UnrecognizedOpcode {
-                    opcode: opcode as u64,
-                    pc: self.program_counter as u64,
-                });
-            }
         }
 
         Ok(())
@@ -459,25 +630,40 @@ impl Emulator for Chip8Emulator {
     }
 
     fn load_rom(&mut self, path: &Path) -> Result<(), EmuError> {
-        self.reset();
         let mut file = File::open(path).map_err(|e| EmuError::RomIoError {
             rom: path.to_path_buf(),
             source: e,
         })?;
 
-        let rom_space = &mut self.memory[PROGRAM_START_ADDRESS as usize..];
-        let n = file.read(rom_space).map_err(|e| EmuError::RomIoError {
-            rom: path.to_path_buf(),
-            source: e,
-        })?;
+        let mut rom_data = Vec::new();
+        file.read_to_end(&mut rom_data)
+            .map_err(|e| EmuError::RomIoError {
+                rom: path.to_path_buf(),
+                source: e,
+            })?;
 
-        if n > rom_space.len() {
-            return Err(EmuError::InvalidRom {
+        self.load_program(&rom_data).map_err(|e| match e {
+            EmuError::ProgramTooLarge { .. } => EmuError::InvalidRom {
                 rom: path.to_path_buf(),
                 message: "ROM file is too large",
+            },
+            e => e,
+        })
+    }
+
+    fn load_program(&mut self, program: &[u8]) -> Result<(), EmuError> {
+        let rom_space = &mut self.memory[PROGRAM_START_ADDRESS as usize..];
+        if program.len() > rom_space.len() {
+            return Err(EmuError::ProgramTooLarge {
+                size: program.len(),
+                max: rom_space.len(),
             });
         }
 
+        self.reset();
+        let rom_space = &mut self.memory[PROGRAM_START_ADDRESS as usize..];
+        rom_space.fill(0);
+        rom_space[..program.len()].copy_from_slice(program);
         Ok(())
     }
 
@@ -489,33 +675,130 @@ impl Emulator for Chip8Emulator {
         self.sound_timer = 0;
         self.registers = [0; 16];
         self.buffer = [false; WIDTH * HEIGHT];
-        self.framebuffer = [0; WIDTH * HEIGHT];
+        self.dirty_rows = [true; HEIGHT];
+        self.last_draw = None;
+        self.call_trace.clear();
+        self.lenient_fixups = 0;
+        self.timer_accumulator = Duration::ZERO;
+        self.vblank_wait = false;
+        self.input_keys = [false; 16];
+        self.is_key_pressed = false;
+        self.lcg = Lcg::new(75, 1, self.seed);
     }
 
     fn step(&mut self) -> Result<(), EmuError> {
-        self.current_opcode = (self.memory[self.program_counter as usize] as u16) << 8
-            | self.memory[self.program_counter as usize + 1] as u16;
-        self.program_counter = self.program_counter.wrapping_add(2);
-        self.parse_opcode(self.current_opcode)?;
-        self.update_framebuffer();
+        if self.vblank_wait {
+            self.last_cycles = 0;
+            return Ok(());
+        }
+
+        let pc = self.program_counter;
+        if pc as usize + 1 >= self.memory.len() {
+            return Err(EmuError::InvalidMemoryAccess {
+                addr: pc as u64,
+                pc: pc as u64,
+            });
+        }
+
+        self.current_opcode =
+            (self.memory[pc as usize] as u16) << 8 | self.memory[pc as usize + 1] as u16;
+        self.program_counter = pc.wrapping_add(2);
+        if let Err(e) = self.parse_opcode(self.current_opcode) {
+            // Leave PC on the faulting instruction so it can be inspected or skipped
+            self.program_counter = pc;
+            return Err(e);
+        }
+        self.last_cycles = self.vip_cycles(self.current_opcode);
         Ok(())
     }
 
-    fn update_timers(&mut self, _delta: Duration) {
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
+    fn seed(&self) -> u8 {
+        self.seed
+    }
+
+    fn set_seed(&mut self, seed: u8) {
+        self.seed = seed;
+    }
+
+    fn set_display_wait(&mut self, display_wait: bool) {
+        self.display_wait = display_wait;
+        self.vblank_wait &= display_wait;
+    }
+
+    fn set_palette(&mut self, colors: [u32; 4]) {
+        self.palette = colors;
+        self.dirty_rows = [true; HEIGHT];
+    }
+
+    fn set_policy(&mut self, policy: ExecutionPolicy) {
+        self.policy = policy;
+    }
+
+    fn skip_instruction(&mut self) {
+        self.program_counter = self.program_counter.wrapping_add(2);
+    }
+
+    fn last_instruction_cycles(&self) -> u32 {
+        self.last_cycles
+    }
+
+    fn update_timers(&mut self, delta: Duration) {
+        // Count down once per whole 60 Hz period, keeping the remainder
+        self.timer_accumulator += delta;
+        let ticks = (self.timer_accumulator.as_nanos() / TIMER_PERIOD.as_nanos()) as u32;
+        if ticks == 0 {
+            return;
         }
+        self.timer_accumulator -= TIMER_PERIOD * ticks;
+        self.vblank_wait = false;
+        let ticks = ticks.min(u8::MAX as u32) as u8;
+
+        self.delay_timer = self.delay_timer.saturating_sub(ticks);
 
         if self.sound_timer > 0 {
             if !self.is_mute {
-                let _ = self.audio.play(true); // Ignore errors
+                let _ = self.audio().play(true); // Ignore errors
             }
-            self.sound_timer -= 1;
-        } else if !self.is_mute {
-            let _ = self.audio.pause(); // Ignore errors
+            self.sound_timer = self.sound_timer.saturating_sub(ticks);
+        } else if let Some(audio) = &mut self.audio {
+            let _ = audio.pause(); // Ignore errors
+        }
+    }
+
+    fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    fn set_mute(&mut self, mute: bool) {
+        self.is_mute = mute;
+        if let (true, Some(audio)) = (mute, &self.audio) {
+            let _ = audio.pause(); // Ignore errors
         }
     }
 
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        if let Some(audio) = &self.audio {
+            let _ = audio.volume(volume); // Ignore errors
+        }
+    }
+
+    fn waiting_for_vblank(&self) -> bool {
+        self.vblank_wait
+    }
+
+    fn end_vblank_wait(&mut self) {
+        self.vblank_wait = false;
+    }
+
+    fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    fn frame_changed(&mut self) -> bool {
+        self.update_framebuffer()
+    }
+
     fn framebuffer(&self) -> &[u32] {
         &self.framebuffer
     }
@@ -528,6 +811,23 @@ impl Emulator for Chip8Emulator {
         self.input_keys.copy_from_slice(&inputs[0..16.min(inputs.len())]);
     }
 
+    fn gamepad_map(&self) -> Vec<(usize, String)> {
+        // Up/left/down/right and action as on W/A/S/D and E
+        vec![
+            (0x5, "DPadUp".to_string()),
+            (0x7, "DPadLeft".to_string()),
+            (0x8, "DPadDown".to_string()),
+            (0x9, "DPadRight".to_string()),
+            (0x6, "South".to_string()),
+            (0x4, "East".to_string()),
+            (0xF, "Start".to_string()),
+        ]
+    }
+
+    fn button_names(&self) -> Vec<String> {
+        (0..16).map(|key| format!("{:X}", key)).collect()
+    }
+
     fn keymap(&self) -> Vec<(usize, String)> {
         vec![
             (0x0, "X".to_string()),
@@ -560,6 +860,9 @@ impl Emulator for Chip8Emulator {
             sound_timer: self.sound_timer,
             current_opcode: self.current_opcode,
             memory: self.memory,
+            last_draw: self.last_draw.clone(),
+            call_trace: self.call_trace.iter().copied().collect(),
+            lenient_fixups: self.lenient_fixups,
         }
     }
 }
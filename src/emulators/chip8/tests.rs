@@ -0,0 +1,744 @@
+use super::configs::{FONTSET_START_ADDRESS, HEIGHT, PROGRAM_START_ADDRESS, TIMER_PERIOD, WIDTH};
+use super::rand::Lcg;
+use super::{Chip8Emulator, StackEvent};
+use crate::emulators::{EmuError, Emulator, ExecutionPolicy};
+use proptest::prelude::*;
+
+const START: u16 = PROGRAM_START_ADDRESS;
+
+fn with_profile(policy: ExecutionPolicy, display_wait: bool, program: &[u16]) -> Chip8Emulator {
+    let mut emulator = Chip8Emulator::new();
+    emulator.set_mute(true);
+    emulator.set_policy(policy);
+    emulator.set_display_wait(display_wait);
+    let bytes: Vec<u8> = program.iter().flat_map(|op| op.to_be_bytes()).collect();
+    emulator.load_program(&bytes).unwrap();
+    emulator
+}
+
+/// Lenient emulator without display wait, running `program`
+fn load(program: &[u16]) -> Chip8Emulator {
+    with_profile(ExecutionPolicy::Lenient, false, program)
+}
+
+/// `program` loaded under every combination of policy and display wait
+fn profiles(program: &[u16]) -> Vec<Chip8Emulator> {
+    ExecutionPolicy::ALL
+        .into_iter()
+        .flat_map(|policy| [false, true].map(|wait| with_profile(policy, wait, program)))
+        .collect()
+}
+
+fn run(emulator: &mut Chip8Emulator, steps: usize) {
+    for _ in 0..steps {
+        emulator.step().unwrap();
+    }
+}
+
+fn pixel(emulator: &Chip8Emulator, x: usize, y: usize) -> bool {
+    emulator.buffer[y * WIDTH + x]
+}
+
+#[test]
+fn cls_clears_display() {
+    for mut emulator in profiles(&[0x00E0]) {
+        emulator.buffer = [true; WIDTH * HEIGHT];
+        emulator.dirty_rows = [false; HEIGHT];
+        run(&mut emulator, 1);
+        assert!(emulator.buffer.iter().all(|&pixel| !pixel));
+        assert!(emulator.dirty_rows.iter().all(|&dirty| dirty));
+    }
+}
+
+#[test]
+fn call_and_return() {
+    // 200: CALL 206, 202: LD V1 1, 204: JP 204, 206: LD V0 5, 208: RET
+    for mut emulator in profiles(&[0x2206, 0x6101, 0x1204, 0x6005, 0x00EE]) {
+        run(&mut emulator, 1);
+        assert_eq!(emulator.program_counter, 0x206);
+        assert_eq!(emulator.stack_pointer, 1);
+        assert_eq!(emulator.stack[0], 0x202);
+
+        run(&mut emulator, 3);
+        assert_eq!(emulator.stack_pointer, 0);
+        assert_eq!(emulator.registers[0], 5);
+        assert_eq!(emulator.registers[1], 1);
+        assert_eq!(
+            emulator.metadata().call_trace,
+            vec![
+                StackEvent::Call {
+                    from: 0x200,
+                    target: 0x206
+                },
+                StackEvent::Return {
+                    from: 0x208,
+                    to: 0x202
+                },
+            ]
+        );
+    }
+}
+
+#[test]
+fn call_overflow_is_an_error() {
+    for mut emulator in profiles(&[0x2200]) {
+        run(&mut emulator, 16);
+        let result = emulator.step();
+        assert!(matches!(
+            result,
+            Err(EmuError::InvalidStackAccess { sp: 16, pc: 0x200 })
+        ));
+        assert_eq!(emulator.program_counter, 0x200);
+    }
+}
+
+#[test]
+fn return_underflow_is_an_error() {
+    for mut emulator in profiles(&[0x00EE]) {
+        assert!(matches!(
+            emulator.step(),
+            Err(EmuError::InvalidStackAccess { pc: 0x200, .. })
+        ));
+        assert_eq!(emulator.program_counter, START);
+    }
+}
+
+#[test]
+fn sys_is_skipped_when_lenient() {
+    let mut emulator = load(&[0x0123, 0x6001]);
+    run(&mut emulator, 2);
+    assert_eq!(emulator.registers[0], 1);
+    assert_eq!(emulator.lenient_fixups, 1);
+}
+
+#[test]
+fn sys_is_an_error_when_strict() {
+    for opcode in [0x0000, 0x0123, 0x0FFF] {
+        let mut emulator = with_profile(ExecutionPolicy::Strict, false, &[opcode]);
+        assert!(matches!(
+            emulator.step(),
+            Err(EmuError::InvalidOpcodeUsage { pc: 0x200, .. })
+        ));
+        assert_eq!(emulator.program_counter, START);
+    }
+}
+
+#[test]
+fn jump() {
+    for mut emulator in profiles(&[0x1ABC]) {
+        run(&mut emulator, 1);
+        assert_eq!(emulator.program_counter, 0xABC);
+    }
+}
+
+#[test]
+fn jump_with_offset() {
+    for mut emulator in profiles(&[0x6004, 0xB300]) {
+        run(&mut emulator, 2);
+        assert_eq!(emulator.program_counter, 0x304);
+    }
+}
+
+#[test]
+fn skip_on_byte() {
+    // (opcode, V0, skips)
+    let cases = [
+        (0x3005, 5, true),
+        (0x3006, 5, false),
+        (0x4005, 5, false),
+        (0x4006, 5, true),
+    ];
+    for (opcode, value, skips) in cases {
+        for mut emulator in profiles(&[opcode]) {
+            emulator.registers[0] = value;
+            run(&mut emulator, 1);
+            let expected = if skips { START + 4 } else { START + 2 };
+            assert_eq!(emulator.program_counter, expected, "{:04X}", opcode);
+        }
+    }
+}
+
+#[test]
+fn skip_on_register() {
+    // (opcode, V0, V1, skips)
+    let cases = [
+        (0x5010, 3, 3, true),
+        (0x5010, 3, 4, false),
+        (0x9010, 3, 3, false),
+        (0x9010, 3, 4, true),
+    ];
+    for (opcode, v0, v1, skips) in cases {
+        for mut emulator in profiles(&[opcode]) {
+            emulator.registers[0] = v0;
+            emulator.registers[1] = v1;
+            run(&mut emulator, 1);
+            let expected = if skips { START + 4 } else { START + 2 };
+            assert_eq!(emulator.program_counter, expected, "{:04X}", opcode);
+        }
+    }
+}
+
+#[test]
+fn register_skips_reject_any_last_nibble() {
+    for base in [0x5010, 0x9010] {
+        for nibble in 1..=0xF {
+            let opcode = base | nibble;
+
+            // Not a questionable operation but a malformed one, so even
+            // the lenient policy refuses it
+            for mut emulator in profiles(&[opcode]) {
+                assert!(
+                    matches!(emulator.step(), Err(EmuError::InvalidOpcodeUsage { .. })),
+                    "{:04X}",
+                    opcode
+                );
+                assert_eq!(emulator.lenient_fixups, 0);
+            }
+        }
+    }
+}
+
+#[test]
+fn load_and_add_byte() {
+    for mut emulator in profiles(&[0x6AFF, 0x6F07, 0x7A02]) {
+        run(&mut emulator, 3);
+        // Wraps without touching VF
+        assert_eq!(emulator.registers[0xA], 0x01);
+        assert_eq!(emulator.registers[0xF], 0x07);
+    }
+}
+
+/// Run 8XYn with V0 = `vx`, V1 = `vy` and VF = 0xAA, returning V0 and VF
+fn alu(opcode: u16, vx: u8, vy: u8) -> Vec<(u8, u8)> {
+    profiles(&[opcode])
+        .into_iter()
+        .map(|mut emulator| {
+            emulator.registers[0] = vx;
+            emulator.registers[1] = vy;
+            emulator.registers[0xF] = 0xAA;
+            run(&mut emulator, 1);
+            (emulator.registers[0], emulator.registers[0xF])
+        })
+        .collect()
+}
+
+fn assert_alu(opcode: u16, vx: u8, vy: u8, expected: (u8, u8)) {
+    for result in alu(opcode, vx, vy) {
+        assert_eq!(
+            result, expected,
+            "{:04X} with {:02X}, {:02X}",
+            opcode, vx, vy
+        );
+    }
+}
+
+#[test]
+fn logic_resets_vf() {
+    assert_alu(0x8010, 0x0C, 0x0A, (0x0A, 0xAA));
+    assert_alu(0x8011, 0x0C, 0x0A, (0x0E, 0));
+    assert_alu(0x8012, 0x0C, 0x0A, (0x08, 0));
+    assert_alu(0x8013, 0x0C, 0x0A, (0x06, 0));
+}
+
+#[test]
+fn add_sets_carry() {
+    assert_alu(0x8014, 0x01, 0x01, (0x02, 0));
+    assert_alu(0x8014, 0xFF, 0x01, (0x00, 1));
+    assert_alu(0x8014, 0x80, 0x80, (0x00, 1));
+}
+
+#[test]
+fn subtract_sets_not_borrow() {
+    assert_alu(0x8015, 0x05, 0x03, (0x02, 1));
+    assert_alu(0x8015, 0x03, 0x05, (0xFE, 0));
+    assert_alu(0x8015, 0x05, 0x05, (0x00, 1));
+    assert_alu(0x8017, 0x03, 0x05, (0x02, 1));
+    assert_alu(0x8017, 0x05, 0x03, (0xFE, 0));
+    assert_alu(0x8017, 0x05, 0x05, (0x00, 1));
+}
+
+#[test]
+fn shifts_use_vy() {
+    assert_alu(0x8016, 0xFF, 0x05, (0x02, 1));
+    assert_alu(0x8016, 0xFF, 0x04, (0x02, 0));
+    assert_alu(0x801E, 0x00, 0x81, (0x02, 1));
+    assert_alu(0x801E, 0x00, 0x41, (0x82, 0));
+}
+
+#[test]
+fn flag_wins_when_vf_is_the_target() {
+    // VF = 0xFF + 1, the carry overwrites the sum
+    let mut emulator = load(&[0x6FFF, 0x6101, 0x8F14]);
+    run(&mut emulator, 3);
+    assert_eq!(emulator.registers[0xF], 1);
+
+    // VF = 0x05 - 0x03, the not-borrow overwrites the difference
+    let mut emulator = load(&[0x6F05, 0x6103, 0x8F15]);
+    run(&mut emulator, 3);
+    assert_eq!(emulator.registers[0xF], 1);
+}
+
+#[test]
+fn unknown_alu_operations_are_errors() {
+    for n in [0x8, 0x9, 0xA, 0xB, 0xC, 0xD, 0xF] {
+        for mut emulator in profiles(&[0x8010 | n]) {
+            assert!(matches!(
+                emulator.step(),
+                Err(EmuError::InvalidOpcodeUsage { pc: 0x200, .. })
+            ));
+        }
+    }
+}
+
+#[test]
+fn load_index() {
+    for mut emulator in profiles(&[0xA123]) {
+        run(&mut emulator, 1);
+        assert_eq!(emulator.index_register, 0x123);
+    }
+}
+
+#[test]
+fn random_is_masked_and_seeded() {
+    for seed in [0, 31, 200] {
+        for mut emulator in profiles(&[0xC0FF, 0xC10F, 0xC200]) {
+            emulator.set_seed(seed);
+            emulator.reset();
+            run(&mut emulator, 3);
+
+            let mut lcg = Lcg::new(75, 1, seed);
+            assert_eq!(emulator.registers[0], lcg.next());
+            assert_eq!(emulator.registers[1], lcg.next() & 0x0F);
+            assert_eq!(emulator.registers[2], 0);
+        }
+    }
+}
+
+#[test]
+fn draw_toggles_pixels_and_reports_collisions() {
+    // Font digit 0 at (2, 3), drawn twice
+    let program = [0x6000, 0xF029, 0x6202, 0x6303, 0xD235, 0xD235];
+    for mut emulator in profiles(&program) {
+        run(&mut emulator, 5);
+        assert_eq!(emulator.registers[0xF], 0);
+        // 0xF0, 0x90, 0x90, 0x90, 0xF0
+        for row in 0..5 {
+            let full = row == 0 || row == 4;
+            assert!(pixel(&emulator, 2, 3 + row));
+            assert_eq!(pixel(&emulator, 3, 3 + row), full);
+            assert!(pixel(&emulator, 5, 3 + row));
+            assert!(!pixel(&emulator, 6, 3 + row));
+        }
+
+        // Display wait holds the second draw until the next vertical blank
+        emulator.update_timers(TIMER_PERIOD);
+        run(&mut emulator, 1);
+        assert_eq!(emulator.registers[0xF], 1);
+        assert!(emulator.buffer.iter().all(|&pixel| !pixel));
+    }
+}
+
+#[test]
+fn draw_wraps_start_position_and_sprite_edges() {
+    // A full row at (64 + 60, 32 + 2): starts at (60, 2) and wraps to x = 0..3
+    let program = [0xA300, 0x607C, 0x6122, 0xD011];
+    for mut emulator in profiles(&program) {
+        emulator.memory[0x300] = 0xFF;
+        run(&mut emulator, 4);
+        for x in (60..64).chain(0..4) {
+            assert!(pixel(&emulator, x, 2), "x = {}", x);
+        }
+        assert_eq!(emulator.buffer.iter().filter(|&&pixel| pixel).count(), 8);
+    }
+}
+
+#[test]
+fn display_wait_stalls_until_vblank() {
+    for display_wait in [false, true] {
+        let mut emulator = with_profile(ExecutionPolicy::Lenient, display_wait, &[0xD001, 0x6001]);
+        run(&mut emulator, 2);
+        assert_eq!(emulator.waiting_for_vblank(), display_wait);
+        assert_eq!(emulator.registers[0], u8::from(!display_wait));
+
+        emulator.update_timers(TIMER_PERIOD);
+        assert!(!emulator.waiting_for_vblank());
+        run(&mut emulator, 1);
+        assert_eq!(emulator.registers[0], 1);
+    }
+}
+
+#[test]
+fn ending_the_vblank_wait_leaves_the_timers_alone() {
+    // LD V0, 5; LD DT, V0; DRW V0, V0, 1; LD V1, 1
+    let mut emulator = with_profile(
+        ExecutionPolicy::Lenient,
+        true,
+        &[0x6005, 0xF015, 0xD001, 0x6101],
+    );
+    run(&mut emulator, 3);
+    assert!(emulator.waiting_for_vblank());
+
+    emulator.end_vblank_wait();
+    run(&mut emulator, 1);
+    assert_eq!(emulator.registers[1], 1);
+    assert_eq!(emulator.delay_timer, 5);
+}
+
+#[test]
+fn skip_on_key() {
+    // (opcode, key down, skips)
+    let cases = [
+        (0xE59E, true, true),
+        (0xE59E, false, false),
+        (0xE5A1, true, false),
+        (0xE5A1, false, true),
+    ];
+    for (opcode, down, skips) in cases {
+        for mut emulator in profiles(&[opcode]) {
+            emulator.registers[5] = 0xC;
+            let mut keys = [false; 16];
+            keys[0xC] = down;
+            emulator.set_input_state(&keys);
+            run(&mut emulator, 1);
+            let expected = if skips { START + 4 } else { START + 2 };
+            assert_eq!(emulator.program_counter, expected, "{:04X}", opcode);
+        }
+    }
+}
+
+#[test]
+fn key_operand_out_of_range() {
+    let mut strict = with_profile(ExecutionPolicy::Strict, false, &[0xE09E]);
+    strict.registers[0] = 0x1C;
+    assert!(matches!(
+        strict.step(),
+        Err(EmuError::ValueOutOfRange {
+            value: 0x1C,
+            max: 0xF,
+            pc: 0x200
+        })
+    ));
+
+    // Lenient uses the low nibble
+    let mut lenient = load(&[0xE09E]);
+    lenient.registers[0] = 0x1C;
+    let mut keys = [false; 16];
+    keys[0xC] = true;
+    lenient.set_input_state(&keys);
+    run(&mut lenient, 1);
+    assert_eq!(lenient.program_counter, START + 4);
+    assert_eq!(lenient.lenient_fixups, 1);
+}
+
+#[test]
+fn unknown_key_operations_are_errors() {
+    for mut emulator in profiles(&[0xE000]) {
+        assert!(matches!(
+            emulator.step(),
+            Err(EmuError::InvalidOpcodeUsage { .. })
+        ));
+    }
+}
+
+#[test]
+fn timers_count_down_at_60_hz() {
+    for mut emulator in profiles(&[0x6003, 0xF015, 0xF018, 0xF107]) {
+        run(&mut emulator, 3);
+        assert_eq!(emulator.delay_timer, 3);
+        assert_eq!(emulator.sound_timer, 3);
+        assert!(emulator.sound_active());
+
+        emulator.update_timers(TIMER_PERIOD / 2);
+        assert_eq!(emulator.delay_timer, 3);
+        emulator.update_timers(TIMER_PERIOD / 2 + TIMER_PERIOD);
+        assert_eq!(emulator.delay_timer, 1);
+
+        run(&mut emulator, 1);
+        assert_eq!(emulator.registers[1], 1);
+
+        emulator.update_timers(TIMER_PERIOD * 5);
+        assert_eq!(emulator.delay_timer, 0);
+        assert_eq!(emulator.sound_timer, 0);
+        assert!(!emulator.sound_active());
+    }
+}
+
+#[test]
+fn wait_for_key_press_and_release() {
+    for mut emulator in profiles(&[0xF30A]) {
+        run(&mut emulator, 3);
+        assert_eq!(emulator.program_counter, START);
+
+        let mut keys = [false; 16];
+        keys[7] = true;
+        emulator.set_input_state(&keys);
+        run(&mut emulator, 3);
+        assert_eq!(emulator.registers[3], 7);
+        assert_eq!(emulator.program_counter, START, "waits for the release");
+
+        emulator.set_input_state(&[false; 16]);
+        run(&mut emulator, 1);
+        assert_eq!(emulator.program_counter, START + 2);
+        assert_eq!(emulator.registers[3], 7);
+    }
+}
+
+#[test]
+fn add_to_index() {
+    for mut emulator in profiles(&[0xAFFF, 0x6002, 0xF01E]) {
+        run(&mut emulator, 3);
+        assert_eq!(emulator.index_register, 0x1001);
+    }
+}
+
+#[test]
+fn font_digit_address() {
+    for digit in 0..16 {
+        for mut emulator in profiles(&[0xF429]) {
+            emulator.registers[4] = digit;
+            run(&mut emulator, 1);
+            assert_eq!(
+                emulator.index_register,
+                FONTSET_START_ADDRESS + digit as u16 * 5
+            );
+        }
+    }
+}
+
+#[test]
+fn binary_coded_decimal() {
+    for (value, digits) in [(254, [2, 5, 4]), (7, [0, 0, 7]), (100, [1, 0, 0])] {
+        for mut emulator in profiles(&[0xA300, 0xF233]) {
+            emulator.registers[2] = value;
+            run(&mut emulator, 2);
+            assert_eq!(emulator.memory[0x300..0x303], digits);
+            assert_eq!(emulator.index_register, 0x300);
+        }
+    }
+}
+
+#[test]
+fn store_and_load_registers_advance_index() {
+    for mut emulator in profiles(&[0xA300, 0xF355, 0xA300, 0xF265]) {
+        emulator.registers[..4].copy_from_slice(&[1, 2, 3, 4]);
+        run(&mut emulator, 2);
+        assert_eq!(emulator.memory[0x300..0x305], [1, 2, 3, 4, 0]);
+        assert_eq!(emulator.index_register, 0x304);
+
+        emulator.registers[..4].fill(0);
+        run(&mut emulator, 2);
+        assert_eq!(emulator.registers[..4], [1, 2, 3, 0]);
+        assert_eq!(emulator.index_register, 0x303);
+    }
+}
+
+#[test]
+fn index_past_end_of_memory() {
+    let mut strict = with_profile(ExecutionPolicy::Strict, false, &[0xAFFE, 0xF355]);
+    run(&mut strict, 1);
+    assert!(matches!(
+        strict.step(),
+        Err(EmuError::IndexOutOfBounds {
+            addr: 0x1001,
+            pc: 0x202
+        })
+    ));
+    assert_eq!(strict.program_counter, 0x202);
+
+    // Lenient wraps around to the start of memory
+    let mut lenient = load(&[0xAFFE, 0xF355]);
+    lenient.registers[..4].copy_from_slice(&[1, 2, 3, 4]);
+    run(&mut lenient, 2);
+    assert_eq!(lenient.memory[0xFFE..], [1, 2]);
+    assert_eq!(lenient.memory[..2], [3, 4]);
+    assert_eq!(lenient.lenient_fixups, 1);
+}
+
+#[test]
+fn unknown_misc_operations_are_errors() {
+    for mut emulator in profiles(&[0xF0FF]) {
+        assert!(matches!(
+            emulator.step(),
+            Err(EmuError::InvalidOpcodeUsage { .. })
+        ));
+    }
+}
+
+#[test]
+fn running_off_the_end_of_memory() {
+    for mut emulator in profiles(&[0x1FFF]) {
+        run(&mut emulator, 1);
+        assert!(matches!(
+            emulator.step(),
+            Err(EmuError::InvalidMemoryAccess { addr: 0xFFF, .. })
+        ));
+    }
+}
+
+/// Straightforward model of the register, skip, timer and memory
+/// instructions, written independently of `parse_opcode`
+#[derive(Debug, Clone, PartialEq)]
+struct Model {
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    delay: u8,
+    sound: u8,
+    memory: Vec<u8>,
+}
+
+impl Model {
+    fn of(emulator: &Chip8Emulator) -> Self {
+        Self {
+            v: emulator.registers,
+            i: emulator.index_register,
+            pc: emulator.program_counter,
+            delay: emulator.delay_timer,
+            sound: emulator.sound_timer,
+            memory: emulator.memory.to_vec(),
+        }
+    }
+
+    fn execute(&mut self, opcode: u16) {
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+        let nn = opcode as u8;
+        let nnn = opcode & 0xFFF;
+        let skip = |pc: &mut u16, condition: bool| *pc += if condition { 2 } else { 0 };
+        let address = |i: u16, offset: usize| (i as usize + offset) % 4096;
+
+        self.pc += 2;
+        match opcode >> 12 {
+            0x1 => self.pc = nnn,
+            0x3 => skip(&mut self.pc, self.v[x] == nn),
+            0x4 => skip(&mut self.pc, self.v[x] != nn),
+            0x5 => skip(&mut self.pc, self.v[x] == self.v[y]),
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => {
+                let (vx, vy) = (self.v[x] as u16, self.v[y] as u16);
+                let (result, flag) = match opcode & 0xF {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, Some(0)),
+                    0x2 => (vx & vy, Some(0)),
+                    0x3 => (vx ^ vy, Some(0)),
+                    0x4 => (vx + vy, Some(u8::from(vx + vy > 0xFF))),
+                    0x5 => (vx.wrapping_sub(vy), Some(u8::from(vx >= vy))),
+                    0x6 => (vy >> 1, Some(vy as u8 & 1)),
+                    0x7 => (vy.wrapping_sub(vx), Some(u8::from(vy >= vx))),
+                    0xE => (vy << 1, Some((vy >> 7) as u8)),
+                    _ => unreachable!("not generated"),
+                };
+                self.v[x] = result as u8;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            }
+            0x9 => skip(&mut self.pc, self.v[x] != self.v[y]),
+            0xA => self.i = nnn,
+            0xB => self.pc = nnn + self.v[0] as u16,
+            0xF => match nn {
+                0x07 => self.v[x] = self.delay,
+                0x15 => self.delay = self.v[x],
+                0x18 => self.sound = self.v[x],
+                0x1E => self.i = self.i.wrapping_add(self.v[x] as u16),
+                0x29 => self.i = 0x50 + (self.v[x] & 0xF) as u16 * 5,
+                0x33 => {
+                    let value = self.v[x];
+                    for (offset, digit) in [value / 100, value / 10 % 10, value % 10]
+                        .into_iter()
+                        .enumerate()
+                    {
+                        self.memory[address(self.i, offset)] = digit;
+                    }
+                }
+                0x55 => {
+                    for offset in 0..=x {
+                        self.memory[address(self.i, offset)] = self.v[offset];
+                    }
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+                0x65 => {
+                    for offset in 0..=x {
+                        self.v[offset] = self.memory[address(self.i, offset)];
+                    }
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+                _ => unreachable!("not generated"),
+            },
+            _ => unreachable!("not generated"),
+        }
+    }
+}
+
+/// Opcodes the model covers
+fn modelled_opcode() -> impl Strategy<Value = u16> {
+    let xy = 0u16..0x100;
+    prop_oneof![
+        0x1000u16..0x2000,
+        0x3000u16..0x5000,
+        xy.clone().prop_map(|xy| 0x5000 | xy << 4),
+        0x6000u16..0x8000,
+        (
+            xy.clone(),
+            prop::sample::select(vec![0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE])
+        )
+            .prop_map(|(xy, n)| 0x8000 | xy << 4 | n),
+        xy.prop_map(|xy| 0x9000 | xy << 4),
+        0xA000u16..0xC000,
+        (
+            0u16..0x10,
+            prop::sample::select(vec![0x07, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55, 0x65])
+        )
+            .prop_map(|(x, nn)| 0xF000 | x << 8 | nn),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn matches_reference_model(
+        registers in any::<[u8; 16]>(),
+        index in 0u16..0x1000,
+        delay in any::<u8>(),
+        opcode in modelled_opcode(),
+    ) {
+        let mut emulator = load(&[opcode]);
+        emulator.registers = registers;
+        emulator.index_register = index;
+        emulator.delay_timer = delay;
+        let mut model = Model::of(&emulator);
+
+        emulator.step().unwrap();
+        model.execute(opcode);
+        prop_assert_eq!(Model::of(&emulator), model);
+    }
+
+    #[test]
+    fn drawing_twice_restores_the_display(
+        display in prop::collection::vec(any::<bool>(), WIDTH * HEIGHT),
+        x in any::<u8>(),
+        y in any::<u8>(),
+        sprite in prop::collection::vec(any::<u8>(), 1..16),
+    ) {
+        let height = sprite.len() as u16;
+        let mut emulator = load(&[0xA300, 0xD010 | height, 0xD010 | height]);
+        emulator.buffer.copy_from_slice(&display);
+        emulator.registers[0] = x;
+        emulator.registers[1] = y;
+        emulator.memory[0x300..0x300 + sprite.len()].copy_from_slice(&sprite);
+
+        run(&mut emulator, 2);
+        // The second draw collides wherever the first one turned a pixel on
+        let lit = (0..sprite.len()).flat_map(|row| (0..8).map(move |col| (row, col)))
+            .filter(|&(row, col)| sprite[row] & 0x80 >> col != 0)
+            .any(|(row, col)| {
+                let screen_x = (x as usize % WIDTH + col) % WIDTH;
+                let screen_y = (y as usize % HEIGHT + row) % HEIGHT;
+                !display[screen_y * WIDTH + screen_x]
+            });
+
+        run(&mut emulator, 1);
+        prop_assert_eq!(&emulator.buffer[..], &display[..]);
+        prop_assert_eq!(emulator.registers[0xF], u8::from(lit));
+    }
+}
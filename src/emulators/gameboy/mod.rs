@@ -1,8 +1,20 @@
-use super::{EmuError, Emulator};
+use super::{EmuError, Emulator, ExecutionPolicy};
 use std::path::Path;
 use std::time::Duration;
 
+/// Where shades 0 (lightest) to 3 (darkest) are found in a palette, which
+/// lists background, plane 1, plane 2 and both planes
+#[allow(
+    dead_code,
+    reason = "the Game Boy skeleton cannot be loaded from the UI yet"
+)]
+const SHADE_ORDER: [usize; 4] = [0, 2, 3, 1];
+
 /// Game Boy specific metadata
+#[allow(
+    dead_code,
+    reason = "the Game Boy skeleton cannot be loaded from the UI yet"
+)]
 #[derive(Debug, Clone)]
 pub struct GameBoyMetadata {
     pub registers: [u8; 8],  // A, F, B, C, D, E, H, L
@@ -12,14 +24,23 @@ pub struct GameBoyMetadata {
 }
 
 /// Game Boy emulator (skeleton)
+#[allow(
+    dead_code,
+    reason = "the Game Boy skeleton cannot be loaded from the UI yet"
+)]
 pub struct GameBoyEmulator {
     registers: [u8; 8],
     sp: u16,
     pc: u16,
     memory: [u8; 0x10000],
     framebuffer: [u32; 160 * 144], // Game Boy screen: 160x144
+    shades: [u32; 4],
 }
 
+#[allow(
+    dead_code,
+    reason = "the Game Boy skeleton cannot be loaded from the UI yet"
+)]
 impl GameBoyEmulator {
     pub fn new() -> Self {
         Self {
@@ -28,6 +49,7 @@ impl GameBoyEmulator {
             pc: 0x0100,
             memory: [0; 0x10000],
             framebuffer: [0xFF000000; 160 * 144], // Black screen
+            shades: [0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555, 0xFF000000],
         }
     }
 }
@@ -55,14 +77,24 @@ impl Emulator for GameBoyEmulator {
             source: e,
         })?;
 
-        if rom_data.len() > 0x8000 {
-            return Err(EmuError::InvalidRom {
+        self.load_program(&rom_data).map_err(|e| match e {
+            EmuError::ProgramTooLarge { .. } => EmuError::InvalidRom {
                 rom: path.to_path_buf(),
                 message: "ROM file is too large (max 32KB for now)",
+            },
+            e => e,
+        })
+    }
+
+    fn load_program(&mut self, program: &[u8]) -> Result<(), EmuError> {
+        if program.len() > 0x8000 {
+            return Err(EmuError::ProgramTooLarge {
+                size: program.len(),
+                max: 0x8000,
             });
         }
 
-        self.memory[0..rom_data.len()].copy_from_slice(&rom_data);
+        self.memory[0..program.len()].copy_from_slice(program);
         Ok(())
     }
 
@@ -79,10 +111,63 @@ impl Emulator for GameBoyEmulator {
         Ok(())
     }
 
+    fn seed(&self) -> u8 {
+        // The Game Boy has no random number generator of its own
+        0
+    }
+
+    fn set_seed(&mut self, _seed: u8) {}
+
+    fn set_display_wait(&mut self, _display_wait: bool) {
+        // The Game Boy has no such quirk, drawing is always vblank driven
+    }
+
+    fn set_palette(&mut self, colors: [u32; 4]) {
+        self.shades = SHADE_ORDER.map(|i| colors[i]);
+    }
+
+    fn set_policy(&mut self, _policy: ExecutionPolicy) {
+        // TODO: Honour the policy once the CPU exists
+    }
+
+    fn skip_instruction(&mut self) {
+        // TODO: Advance by the decoded instruction length once the CPU exists
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    fn last_instruction_cycles(&self) -> u32 {
+        // TODO: Report real instruction timings once the CPU exists
+        4
+    }
+
     fn update_timers(&mut self, _delta: Duration) {
         // TODO: Implement Game Boy timers
     }
 
+    fn sound_active(&self) -> bool {
+        // TODO: Report APU activity once sound is implemented
+        false
+    }
+
+    fn set_mute(&mut self, _mute: bool) {}
+
+    fn set_volume(&mut self, _volume: f32) {}
+
+    fn waiting_for_vblank(&self) -> bool {
+        false
+    }
+
+    fn end_vblank_wait(&mut self) {}
+
+    fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    fn frame_changed(&mut self) -> bool {
+        // TODO: Report changes once the PPU exists
+        false
+    }
+
     fn framebuffer(&self) -> &[u32] {
         &self.framebuffer
     }
@@ -95,16 +180,35 @@ impl Emulator for GameBoyEmulator {
         // TODO: Implement Game Boy input handling
     }
 
+    fn gamepad_map(&self) -> Vec<(usize, String)> {
+        vec![
+            (0, "East".to_string()),  // A button
+            (1, "South".to_string()), // B button
+            (2, "Start".to_string()),
+            (3, "Select".to_string()),
+            (4, "DPadUp".to_string()),
+            (5, "DPadDown".to_string()),
+            (6, "DPadLeft".to_string()),
+            (7, "DPadRight".to_string()),
+        ]
+    }
+
+    fn button_names(&self) -> Vec<String> {
+        ["A", "B", "Start", "Select", "Up", "Down", "Left", "Right"]
+            .map(String::from)
+            .to_vec()
+    }
+
     fn keymap(&self) -> Vec<(usize, String)> {
         vec![
-            (0, "X".to_string()),      // A button
-            (1, "Z".to_string()),      // B button
-            (2, "Return".to_string()), // Start
-            (3, "RShift".to_string()), // Select
-            (4, "Up".to_string()),     // D-pad up
-            (5, "Down".to_string()),   // D-pad down
-            (6, "Left".to_string()),   // D-pad left
-            (7, "Right".to_string()),  // D-pad right
+            (0, "X".to_string()),          // A button
+            (1, "Z".to_string()),          // B button
+            (2, "Enter".to_string()),      // Start
+            (3, "Backspace".to_string()),  // Select
+            (4, "ArrowUp".to_string()),    // D-pad up
+            (5, "ArrowDown".to_string()),  // D-pad down
+            (6, "ArrowLeft".to_string()),  // D-pad left
+            (7, "ArrowRight".to_string()), // D-pad right
         ]
     }
 
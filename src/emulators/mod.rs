@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 use std::path::Path;
 use std::time::Duration;
@@ -10,6 +11,7 @@ pub mod gameboy;
 pub enum EmuError {
     /// The CPU encountered an opcode that is not implemented or unknown.
     #[error("Unrecognized opcode {opcode:#06X} at PC={pc:#010X}")]
+    #[allow(dead_code, reason = "reserved for systems other than CHIP-8")]
     UnrecognizedOpcode { opcode: u64, pc: u64 },
 
     /// The opcode was recognized but used in an invalid or contextually incorrect way.
@@ -24,20 +26,31 @@ pub enum EmuError {
     #[error("Invalid stack access SP={sp:#X} at PC={pc:#010X}")]
     InvalidStackAccess { sp: u64, pc: u64 },
 
+    /// Memory access through the index register past the end of memory.
+    #[error("Index register access out of bounds at address {addr:#06X} (PC={pc:#010X})")]
+    IndexOutOfBounds { addr: u64, pc: u64 },
+
+    /// Operand value outside the range an instruction accepts (e.g. key or font digit).
+    #[error("Value {value:#X} out of range 0..={max:#X} at PC={pc:#010X}")]
+    ValueOutOfRange { value: u64, max: u64, pc: u64 },
+
     /// Memory access outside allowed address space.
     #[error("Invalid memory access at address {addr:#010X} (PC={pc:#010X})")]
     InvalidMemoryAccess { addr: u64, pc: u64 },
 
     /// Invalid register indexing (e.g., out of range, or unmapped).
     #[error("Invalid register index {index} at PC={pc:#010X}")]
+    #[allow(dead_code, reason = "reserved for systems other than CHIP-8")]
     InvalidRegisterIndex { index: usize, pc: u64 },
 
     /// Arithmetic or logic operation error (overflow, division by zero, etc.).
     #[error("ALU error at PC={pc:#010X}: {details}")]
+    #[allow(dead_code, reason = "reserved for systems other than CHIP-8")]
     AluError { pc: u64, details: &'static str },
 
     /// Catch-all for architecture-specific extensions.
     #[error("{message}")]
+    #[allow(dead_code, reason = "reserved for systems other than CHIP-8")]
     Custom { message: &'static str },
 
     /// Invalid ROM is being loaded
@@ -47,6 +60,10 @@ pub enum EmuError {
         message: &'static str,
     },
 
+    /// Program image does not fit into the system's memory
+    #[error("Program is too large ({size} bytes, max {max})")]
+    ProgramTooLarge { size: usize, max: usize },
+
     #[error("I/O error while loading ROM '{rom:?}': {source}")]
     RomIoError {
         rom: std::path::PathBuf,
@@ -55,6 +72,40 @@ pub enum EmuError {
     },
 }
 
+/// How an emulator reacts to questionable but recoverable operations, such as
+/// memory accesses past the end of memory or reserved opcode bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionPolicy {
+    /// Report every questionable operation as an [`EmuError`]
+    Strict,
+    /// Wrap or ignore like real hardware would, counting each fixup
+    #[default]
+    Lenient,
+}
+
+impl ExecutionPolicy {
+    pub const ALL: [ExecutionPolicy; 2] = [ExecutionPolicy::Strict, ExecutionPolicy::Lenient];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExecutionPolicy::Strict => "strict",
+            ExecutionPolicy::Lenient => "lenient",
+        }
+    }
+}
+
+impl std::str::FromStr for ExecutionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown policy '{}', expected strict or lenient", s))
+    }
+}
+
 pub trait Emulator {
     /// System-specific metadata type
     type Metadata: Any + Send + Sync;
@@ -65,15 +116,68 @@ pub trait Emulator {
     /// Load the ROM
     fn load_rom(&mut self, path: &Path) -> Result<(), EmuError>;
 
+    /// Load a program image that is already in memory (e.g. freshly assembled)
+    fn load_program(&mut self, program: &[u8]) -> Result<(), EmuError>;
+
     /// Reset emulator
     fn reset(&mut self);
 
     /// Execute one CPU cycle
     fn step(&mut self) -> Result<(), EmuError>;
 
-    /// Update timers/audio/etc.
+    /// Initial random number generator state, restored on every reset
+    fn seed(&self) -> u8;
+
+    /// Change the random number generator seed, taking effect on the next reset
+    fn set_seed(&mut self, seed: u8);
+
+    /// Make drawing wait for the next vertical blank (COSMAC VIP quirk:
+    /// DXYN stalls until the next timer tick, at most 60 sprites per second)
+    fn set_display_wait(&mut self, display_wait: bool);
+
+    /// Display colours as ARGB8888: background, plane 1, plane 2 and both
+    /// planes. Systems with shades map them onto these four.
+    fn set_palette(&mut self, colors: [u32; 4]);
+
+    /// Choose how questionable operations are handled
+    fn set_policy(&mut self, policy: ExecutionPolicy);
+
+    /// Move past the instruction at the program counter without executing it,
+    /// e.g. to get past one that faulted
+    fn skip_instruction(&mut self);
+
+    /// Machine cycles the last executed instruction took on the original
+    /// hardware, used when emulating its timing
+    fn last_instruction_cycles(&self) -> u32;
+
+    /// Advance timers/audio/etc. by `delta` of emulated time
     fn update_timers(&mut self, delta: Duration);
 
+    /// Whether the CPU is stalled until the next vertical blank, i.e. the
+    /// next 60 Hz timer tick. `step` does nothing while this is true.
+    fn waiting_for_vblank(&self) -> bool;
+
+    /// Stop waiting for the vertical blank without advancing the timers, so
+    /// single-stepping in a debugger does not change the program's state
+    fn end_vblank_wait(&mut self);
+
+    /// Whether the system is making a sound right now
+    fn sound_active(&self) -> bool;
+
+    /// Silence the sound output
+    fn set_mute(&mut self, mute: bool);
+
+    /// Sound output volume from 0 to 1
+    fn set_volume(&mut self, volume: f32);
+
+    /// The full addressable memory, for debugging views
+    fn memory(&self) -> &[u8];
+
+    /// Bring the framebuffer up to date, converting only what was drawn since
+    /// the previous call. Returns false when nothing changed, so callers can
+    /// skip uploading or encoding the same frame again.
+    fn frame_changed(&mut self) -> bool;
+
     /// Framebuffer as ARGB8888 pixels
     fn framebuffer(&self) -> &[u32];
     fn resolution(&self) -> (usize, usize);
@@ -81,13 +185,21 @@ pub trait Emulator {
     /// Input handling
     fn set_input_state(&mut self, inputs: &[bool]);
 
-    /// Get the keymap for this emulator (index -> egui::Key)
+    /// Default keymap for this emulator (button index -> egui key name)
     fn keymap(&self) -> Vec<(usize, String)>;
 
+    /// Default controller bindings (button index -> controller button name,
+    /// see [`crate::gamepad::PadButton`])
+    fn gamepad_map(&self) -> Vec<(usize, String)>;
+
+    /// Names of the emulated buttons, indexed like the input array
+    fn button_names(&self) -> Vec<String>;
+
     /// Access typed metadata - returns a copy/clone
     fn metadata(&self) -> Self::Metadata;
 
     /// Dynamic metadata access (for UI code to downcast)
+    #[allow(dead_code, reason = "the UI only shows CHIP-8 metadata so far")]
     fn metadata_any(&self) -> Box<dyn Any> {
         Box::new(self.metadata())
     }
@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How far a stick has to be pushed to count as a D-pad direction
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// Controller buttons in the standard (SDL) layout, named after their
+/// position rather than their label
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum PadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftTrigger,
+    RightTrigger,
+    LeftTrigger2,
+    RightTrigger2,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl PadButton {
+    pub const ALL: [PadButton; 17] = [
+        PadButton::South,
+        PadButton::East,
+        PadButton::West,
+        PadButton::North,
+        PadButton::LeftTrigger,
+        PadButton::RightTrigger,
+        PadButton::LeftTrigger2,
+        PadButton::RightTrigger2,
+        PadButton::Select,
+        PadButton::Start,
+        PadButton::Mode,
+        PadButton::LeftThumb,
+        PadButton::RightThumb,
+        PadButton::DPadUp,
+        PadButton::DPadDown,
+        PadButton::DPadLeft,
+        PadButton::DPadRight,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PadButton::South => "South",
+            PadButton::East => "East",
+            PadButton::West => "West",
+            PadButton::North => "North",
+            PadButton::LeftTrigger => "LeftTrigger",
+            PadButton::RightTrigger => "RightTrigger",
+            PadButton::LeftTrigger2 => "LeftTrigger2",
+            PadButton::RightTrigger2 => "RightTrigger2",
+            PadButton::Select => "Select",
+            PadButton::Start => "Start",
+            PadButton::Mode => "Mode",
+            PadButton::LeftThumb => "LeftThumb",
+            PadButton::RightThumb => "RightThumb",
+            PadButton::DPadUp => "DPadUp",
+            PadButton::DPadDown => "DPadDown",
+            PadButton::DPadLeft => "DPadLeft",
+            PadButton::DPadRight => "DPadRight",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|button| button.name() == name)
+    }
+
+    fn to_gilrs(self) -> gilrs::Button {
+        match self {
+            PadButton::South => gilrs::Button::South,
+            PadButton::East => gilrs::Button::East,
+            PadButton::West => gilrs::Button::West,
+            PadButton::North => gilrs::Button::North,
+            PadButton::LeftTrigger => gilrs::Button::LeftTrigger,
+            PadButton::RightTrigger => gilrs::Button::RightTrigger,
+            PadButton::LeftTrigger2 => gilrs::Button::LeftTrigger2,
+            PadButton::RightTrigger2 => gilrs::Button::RightTrigger2,
+            PadButton::Select => gilrs::Button::Select,
+            PadButton::Start => gilrs::Button::Start,
+            PadButton::Mode => gilrs::Button::Mode,
+            PadButton::LeftThumb => gilrs::Button::LeftThumb,
+            PadButton::RightThumb => gilrs::Button::RightThumb,
+            PadButton::DPadUp => gilrs::Button::DPadUp,
+            PadButton::DPadDown => gilrs::Button::DPadDown,
+            PadButton::DPadLeft => gilrs::Button::DPadLeft,
+            PadButton::DPadRight => gilrs::Button::DPadRight,
+        }
+    }
+}
+
+/// Where controller state comes from
+pub trait GamepadSource {
+    /// Process pending controller events and return the buttons held down on
+    /// any connected controller
+    fn poll(&mut self) -> BTreeSet<PadButton>;
+
+    /// Names of the connected controllers
+    fn connected(&self) -> Vec<String>;
+}
+
+/// Physical controllers, through evdev on Linux
+pub struct GilrsSource {
+    gilrs: gilrs::Gilrs,
+}
+
+impl GilrsSource {
+    pub fn new() -> Result<Self, String> {
+        gilrs::Gilrs::new()
+            .map(|gilrs| Self { gilrs })
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl GamepadSource for GilrsSource {
+    fn poll(&mut self) -> BTreeSet<PadButton> {
+        while let Some(event) = self.gilrs.next_event() {
+            if let gilrs::EventType::Connected | gilrs::EventType::Disconnected = event.event {
+                let gamepad = self.gilrs.gamepad(event.id);
+                log::info!("Controller {:?}: {}", event.event, gamepad.name());
+            }
+        }
+
+        let mut held = BTreeSet::new();
+        for (_, gamepad) in self.gilrs.gamepads() {
+            held.extend(
+                PadButton::ALL
+                    .into_iter()
+                    .filter(|button| gamepad.is_pressed(button.to_gilrs())),
+            );
+            // The left stick doubles as the D-pad
+            let x = gamepad.value(gilrs::Axis::LeftStickX);
+            let y = gamepad.value(gilrs::Axis::LeftStickY);
+            if x <= -STICK_THRESHOLD {
+                held.insert(PadButton::DPadLeft);
+            }
+            if x >= STICK_THRESHOLD {
+                held.insert(PadButton::DPadRight);
+            }
+            if y >= STICK_THRESHOLD {
+                held.insert(PadButton::DPadUp);
+            }
+            if y <= -STICK_THRESHOLD {
+                held.insert(PadButton::DPadDown);
+            }
+        }
+        held
+    }
+
+    fn connected(&self) -> Vec<String> {
+        self.gilrs
+            .gamepads()
+            .map(|(_, gamepad)| gamepad.name().to_string())
+            .collect()
+    }
+}
+
+/// A controller driven from code, for tests without hardware. Clones share
+/// their state, so one can be handed over as a source and the other kept to
+/// press buttons.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct VirtualGamepad {
+    held: std::sync::Arc<std::sync::Mutex<BTreeSet<PadButton>>>,
+}
+
+#[cfg(test)]
+impl VirtualGamepad {
+    pub fn press(&self, button: PadButton) {
+        self.held.lock().unwrap().insert(button);
+    }
+
+    pub fn release(&self, button: PadButton) {
+        self.held.lock().unwrap().remove(&button);
+    }
+}
+
+#[cfg(test)]
+impl GamepadSource for VirtualGamepad {
+    fn poll(&mut self) -> BTreeSet<PadButton> {
+        self.held.lock().unwrap().clone()
+    }
+
+    fn connected(&self) -> Vec<String> {
+        vec!["Virtual controller".to_string()]
+    }
+}
+
+/// The emulated button each controller button presses. Several controller
+/// buttons may press the same emulated one.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct GamepadMap {
+    bindings: BTreeMap<PadButton, usize>,
+}
+
+impl GamepadMap {
+    /// Build a map from an emulator's default `(button, controller button
+    /// name)` pairs
+    pub fn from_defaults(defaults: &[(usize, String)]) -> Self {
+        let mut bindings = BTreeMap::new();
+        for (button, name) in defaults {
+            match PadButton::from_name(name) {
+                Some(pad_button) => {
+                    bindings.insert(pad_button, *button);
+                }
+                None => log::warn!("Unknown controller button '{}' in default bindings", name),
+            }
+        }
+        Self { bindings }
+    }
+
+    pub fn button(&self, pad_button: PadButton) -> Option<usize> {
+        self.bindings.get(&pad_button).copied()
+    }
+
+    pub fn bind(&mut self, pad_button: PadButton, button: usize) {
+        self.bindings.insert(pad_button, button);
+    }
+
+    pub fn unbind(&mut self, pad_button: PadButton) {
+        self.bindings.remove(&pad_button);
+    }
+
+    /// This map with the buttons bound in `overrides` bound as there
+    pub fn with_overrides(&self, overrides: &GamepadMap) -> GamepadMap {
+        let mut bindings = self.bindings.clone();
+        bindings.extend(&overrides.bindings);
+        Self { bindings }
+    }
+
+    /// Press the emulated buttons bound to `held` controller buttons, on top
+    /// of what `inputs` already holds
+    pub fn apply(&self, held: &BTreeSet<PadButton>, inputs: &mut [bool]) {
+        for pad_button in held {
+            if let Some(input) = self
+                .button(*pad_button)
+                .and_then(|button| inputs.get_mut(button))
+            {
+                *input = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulators::chip8::Chip8Emulator;
+    use crate::emulators::gameboy::GameBoyEmulator;
+    use crate::emulators::Emulator;
+
+    #[test]
+    fn default_bindings_use_known_buttons() {
+        for defaults in [
+            Chip8Emulator::new().gamepad_map(),
+            GameBoyEmulator::new().gamepad_map(),
+        ] {
+            let map = GamepadMap::from_defaults(&defaults);
+            assert_eq!(map.bindings.len(), defaults.len());
+        }
+    }
+
+    #[test]
+    fn virtual_gamepad_presses_bound_keys() {
+        let map = GamepadMap::from_defaults(&Chip8Emulator::new().gamepad_map());
+        let gamepad = VirtualGamepad::default();
+        let mut source: Box<dyn GamepadSource> = Box::new(gamepad.clone());
+
+        gamepad.press(PadButton::DPadUp);
+        gamepad.press(PadButton::South);
+        let mut inputs = [false; 16];
+        map.apply(&source.poll(), &mut inputs);
+        let pressed: Vec<usize> = (0..16).filter(|&key| inputs[key]).collect();
+        assert_eq!(pressed, vec![0x5, 0x6]);
+
+        gamepad.release(PadButton::DPadUp);
+        let mut inputs = [false; 16];
+        map.apply(&source.poll(), &mut inputs);
+        assert_eq!(
+            (0..16).filter(|&key| inputs[key]).collect::<Vec<_>>(),
+            vec![0x6]
+        );
+    }
+
+    #[test]
+    fn keyboard_and_controller_inputs_merge() {
+        let mut map = GamepadMap::default();
+        map.bind(PadButton::East, 0xA);
+        let mut inputs = [false; 16];
+        inputs[0x1] = true;
+        map.apply(
+            &BTreeSet::from([PadButton::East, PadButton::North]),
+            &mut inputs,
+        );
+        assert!(inputs[0x1] && inputs[0xA]);
+        assert_eq!(inputs.iter().filter(|&&held| held).count(), 2);
+    }
+
+    #[test]
+    fn overrides_replace_single_buttons() {
+        let defaults = GamepadMap::from_defaults(&Chip8Emulator::new().gamepad_map());
+        let mut overrides = GamepadMap::default();
+        overrides.bind(PadButton::DPadUp, 0x2);
+        let map = defaults.with_overrides(&overrides);
+        assert_eq!(map.button(PadButton::DPadUp), Some(0x2));
+        assert_eq!(map.button(PadButton::DPadDown), Some(0x8));
+        assert_eq!(defaults.button(PadButton::DPadUp), Some(0x5));
+    }
+
+    #[test]
+    fn maps_round_trip_through_toml() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            map: GamepadMap,
+        }
+        let map = GamepadMap::from_defaults(&GameBoyEmulator::new().gamepad_map());
+        let text = toml::to_string(&Wrapper { map: map.clone() }).unwrap();
+        let parsed: Wrapper = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.map, map);
+    }
+}
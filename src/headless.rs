@@ -0,0 +1,83 @@
+use crate::emulators::chip8::configs::TIMER_PERIOD;
+use crate::emulators::chip8::Chip8Emulator;
+use crate::emulators::{EmuError, Emulator};
+use crate::movie::Movie;
+use crate::recording::{RecordError, Recorder};
+use crate::scheduler::{Clock, Scheduler};
+use crate::screenshot::{self, ScreenshotError};
+use std::path::Path;
+
+/// An emulator driven without a window, one 60 Hz frame at a time, for
+/// scripted runs like screenshots and recordings
+pub struct Headless {
+    pub emulator: Chip8Emulator,
+    scheduler: Scheduler,
+    /// Frames run so far
+    pub frame: u64,
+    /// Input for every frame, released once the movie ends
+    movie: Option<Movie>,
+}
+
+impl Headless {
+    pub fn new(emulator: Chip8Emulator, clock: Clock) -> Self {
+        Self {
+            emulator,
+            scheduler: Scheduler::new(clock),
+            frame: 0,
+            movie: None,
+        }
+    }
+
+    /// Drive the keypad from a movie, starting with the next frame
+    pub fn play(&mut self, movie: Movie) {
+        self.movie = Some(movie);
+    }
+
+    /// Ticks in the movie being played
+    pub fn movie_ticks(&self) -> Option<u64> {
+        self.movie.as_ref().map(|movie| movie.ticks() as u64)
+    }
+
+    /// Run one frame worth of instructions and a timer tick
+    pub fn run_frame(&mut self) -> Result<(), EmuError> {
+        if let Some(movie) = &self.movie {
+            let keys = movie.keys(self.frame as usize).unwrap_or_default();
+            self.emulator.set_input_state(&keys);
+        }
+        self.scheduler.advance_by(TIMER_PERIOD);
+        self.scheduler.run_pending(&mut self.emulator, |_| true)?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    pub fn run_frames(&mut self, frames: u64) -> Result<(), EmuError> {
+        for _ in 0..frames {
+            self.run_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Run `frames` frames, recording each one at the native resolution.
+    /// An emulation error ends the recording early, keeping the frames
+    /// captured so far, and is returned.
+    pub fn record(&mut self, path: &Path, frames: u64) -> Result<u64, RecordError> {
+        let (width, height) = self.emulator.resolution();
+        let mut recorder = Recorder::create(path, width, height, 1)?;
+        for _ in 0..frames {
+            if let Err(source) = self.run_frame() {
+                let frames = recorder.finish()?;
+                return Err(RecordError::Emulation { frames, source });
+            }
+            self.emulator.frame_changed();
+            recorder.capture(&self.emulator)?;
+        }
+        recorder.finish()
+    }
+
+    /// Save the display at its native resolution
+    pub fn screenshot(&mut self, path: &Path) -> Result<(), ScreenshotError> {
+        let (width, height) = self.emulator.resolution();
+        self.emulator.frame_changed();
+        screenshot::save_png(path, self.emulator.framebuffer(), width, height, 1)
+    }
+}
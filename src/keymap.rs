@@ -0,0 +1,144 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// The host key bound to every emulated button, indexed like the input
+/// array of [`crate::emulators::Emulator::set_input_state`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Keymap {
+    #[serde(with = "key_names")]
+    bindings: Vec<Option<egui::Key>>,
+}
+
+impl Keymap {
+    /// Build a keymap from an emulator's default `(button, key name)` pairs
+    pub fn from_defaults(defaults: &[(usize, String)]) -> Self {
+        let buttons = defaults
+            .iter()
+            .map(|&(button, _)| button + 1)
+            .max()
+            .unwrap_or(0);
+        let mut bindings = vec![None; buttons];
+        for (button, name) in defaults {
+            bindings[*button] = egui::Key::from_name(name);
+            if bindings[*button].is_none() {
+                log::warn!("Unknown key '{}' in default keymap", name);
+            }
+        }
+        Self { bindings }
+    }
+
+    /// Pad or truncate to `buttons` buttons, e.g. for a keymap saved by an
+    /// older version
+    pub fn resized(mut self, buttons: usize) -> Self {
+        self.bindings.resize(buttons, None);
+        self
+    }
+
+    /// Number of emulated buttons
+    pub fn buttons(&self) -> usize {
+        self.bindings.len()
+    }
+
+    pub fn key(&self, button: usize) -> Option<egui::Key> {
+        self.bindings.get(button).copied().flatten()
+    }
+
+    /// Bind `key` to `button`, taking it away from any other button
+    pub fn bind(&mut self, button: usize, key: egui::Key) {
+        for binding in &mut self.bindings {
+            if *binding == Some(key) {
+                *binding = None;
+            }
+        }
+        if let Some(binding) = self.bindings.get_mut(button) {
+            *binding = Some(key);
+        }
+    }
+
+    pub fn unbind(&mut self, button: usize) {
+        if let Some(binding) = self.bindings.get_mut(button) {
+            *binding = None;
+        }
+    }
+
+    /// Which buttons are held down
+    pub fn pressed(&self, input: &egui::InputState) -> Vec<bool> {
+        self.bindings
+            .iter()
+            .map(|binding| binding.is_some_and(|key| input.key_down(key)))
+            .collect()
+    }
+}
+
+/// Bindings stored by key name, an empty name for an unbound button
+mod key_names {
+    use eframe::egui::Key;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bindings: &[Option<Key>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(bindings.iter().map(|key| key.map_or("", Key::name)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Option<Key>>, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        names
+            .iter()
+            .map(|name| match name.as_str() {
+                "" => Ok(None),
+                name => Key::from_name(name)
+                    .map(Some)
+                    .ok_or_else(|| D::Error::custom(format!("unknown key '{}'", name))),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulators::chip8::Chip8Emulator;
+    use crate::emulators::gameboy::GameBoyEmulator;
+    use crate::emulators::Emulator;
+
+    #[test]
+    fn default_keymaps_use_known_keys() {
+        let chip8 = Keymap::from_defaults(&Chip8Emulator::new().keymap());
+        assert_eq!(chip8.buttons(), 16);
+        assert!((0..16).all(|button| chip8.key(button).is_some()));
+
+        let gameboy = Keymap::from_defaults(&GameBoyEmulator::new().keymap());
+        assert_eq!(gameboy.buttons(), 8);
+        assert!((0..8).all(|button| gameboy.key(button).is_some()));
+    }
+
+    #[test]
+    fn binding_a_key_moves_it() {
+        let mut keymap = Keymap::from_defaults(&Chip8Emulator::new().keymap());
+        keymap.bind(0xF, egui::Key::X);
+        assert_eq!(keymap.key(0xF), Some(egui::Key::X));
+        assert_eq!(keymap.key(0x0), None);
+    }
+
+    #[test]
+    fn keymaps_round_trip_through_toml() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            keymap: Keymap,
+        }
+        let mut keymap = Keymap::from_defaults(&Chip8Emulator::new().keymap());
+        keymap.unbind(3);
+        let text = toml::to_string(&Wrapper {
+            keymap: keymap.clone(),
+        })
+        .unwrap();
+        let parsed: Wrapper = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.keymap, keymap);
+    }
+}
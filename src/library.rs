@@ -0,0 +1,164 @@
+use crate::movie;
+use crate::screenshot;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// ROM file extensions and the system that runs them
+pub const EXTENSIONS: [(&str, &str); 2] = [("ch8", "CHIP-8"), ("gb", "Game Boy")];
+
+/// How many levels of subdirectories are scanned
+const MAX_DEPTH: usize = 4;
+
+/// A ROM found in one of the library directories
+#[derive(Debug, Clone)]
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub system: &'static str,
+    /// File size in bytes
+    pub size: u64,
+    pub sha1: String,
+    /// Last screenshot taken of the ROM
+    pub thumbnail: Option<PathBuf>,
+}
+
+/// The system a ROM file is for, judging by its extension
+pub fn system_for(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(known, _)| *known == ext)
+        .map(|&(_, system)| system)
+}
+
+/// Run [`scan`] on a background thread, as hashing large directories takes
+/// a while. The entries arrive on the returned channel once done.
+pub fn scan_in_background(dirs: Vec<PathBuf>) -> Receiver<Vec<LibraryEntry>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        // The receiver is gone if the scan was restarted
+        let _ = sender.send(scan(&dirs));
+    });
+    receiver
+}
+
+/// Find the ROMs in `dirs` and their subdirectories, sorted by name.
+/// Unreadable files and directories are skipped.
+pub fn scan(dirs: &[PathBuf]) -> Vec<LibraryEntry> {
+    let mut entries = Vec::new();
+    for dir in dirs {
+        scan_dir(dir, 0, &mut entries);
+    }
+    entries.sort_by(|a, b| {
+        a.name
+            .to_lowercase()
+            .cmp(&b.name.to_lowercase())
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    entries
+}
+
+fn scan_dir(dir: &Path, depth: usize, entries: &mut Vec<LibraryEntry>) {
+    let read_dir = match std::fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) => {
+            log::warn!("Cannot scan {:?}: {}", dir, e);
+            return;
+        }
+    };
+
+    // Listed once, screenshots are looked up among the same names
+    let mut files = Vec::new();
+    let mut names = Vec::new();
+    for entry in read_dir.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth < MAX_DEPTH {
+                scan_dir(&path, depth + 1, entries);
+            }
+            continue;
+        }
+        names.push(entry.file_name().to_string_lossy().into_owned());
+        files.push(path);
+    }
+
+    for path in files {
+        let Some(system) = system_for(&path) else {
+            continue;
+        };
+        match std::fs::read(&path) {
+            Ok(data) => entries.push(LibraryEntry {
+                name: path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                system,
+                size: data.len() as u64,
+                sha1: movie::sha1(&data),
+                thumbnail: screenshot::latest(&path, &names),
+                path,
+            }),
+            Err(e) => log::warn!("Cannot read {:?}: {}", path, e),
+        }
+    }
+}
+
+/// File size in B, KiB or MiB
+pub fn format_size(size: u64) -> String {
+    match size {
+        0..1024 => format!("{} B", size),
+        1024..1_048_576 => format!("{:.1} KiB", size as f64 / 1024.0),
+        _ => format!("{:.1} MiB", size as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp directory
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chip8-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn scan_finds_roms_in_subdirectories() {
+        let dir = temp_dir("library");
+        std::fs::create_dir(dir.join("gb")).unwrap();
+        std::fs::write(dir.join("Pong.ch8"), [0x00, 0xE0]).unwrap();
+        std::fs::write(dir.join("gb/tetris.GB"), [0; 32]).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a ROM").unwrap();
+        std::fs::write(dir.join("Pong-20240101-120000.png"), []).unwrap();
+        std::fs::write(dir.join("Pong-20250101-120000.png"), []).unwrap();
+        std::fs::write(dir.join("Pong-backup.png"), []).unwrap();
+
+        let entries = scan_in_background(vec![dir.clone()]).recv().unwrap();
+        let names: Vec<_> = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.system))
+            .collect();
+        assert_eq!(names, [("Pong", "CHIP-8"), ("tetris", "Game Boy")]);
+
+        let pong = &entries[0];
+        assert_eq!(pong.size, 2);
+        assert_eq!(pong.sha1, movie::sha1(&[0x00, 0xE0]));
+        assert_eq!(
+            pong.thumbnail.as_deref(),
+            Some(dir.join("Pong-20250101-120000.png").as_path())
+        );
+        assert_eq!(entries[1].thumbnail, None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sizes_are_human_readable() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(3584), "3.5 KiB");
+        assert_eq!(format_size(2 * 1_048_576), "2.0 MiB");
+    }
+}
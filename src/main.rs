@@ -1,61 +1,1851 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use eframe::egui;
-use std::time::{Duration, Instant};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant, SystemTime};
 
+mod database;
+mod display;
 mod emulators;
-
-use emulators::chip8::{Chip8Emulator, Chip8Metadata};
-use emulators::Emulator;
+mod gamepad;
+mod headless;
+mod keymap;
+mod library;
+mod memory_search;
+mod movie;
+mod recording;
+mod regression;
+mod scheduler;
+mod screenshot;
+mod settings;
+
+use database::{Database, RomInfo};
+use display::{palette, DisplayFilter, FilterMode, Palette, PostProcess};
+use emulators::chip8::analysis::{ByteKind, CodeMap, Line};
+use emulators::chip8::configs::{
+    FONTSET_START_ADDRESS, HEIGHT, PROGRAM_START_ADDRESS, VIP_CYCLES_PER_SECOND, WIDTH,
+};
+use emulators::chip8::{asm, disasm, Chip8Emulator, Chip8Metadata, SpriteDraw, StackEvent};
+use emulators::{EmuError, Emulator, ExecutionPolicy};
+use gamepad::{GamepadMap, GamepadSource, GilrsSource, PadButton};
+use headless::Headless;
+use keymap::Keymap;
+use library::LibraryEntry;
+use memory_search::{ValueFilter, ValueSearch};
+use movie::{Movie, MovieSettings};
+use recording::{RecordFormat, Recorder};
+use scheduler::{Clock, Scheduler};
+use settings::Settings;
 
 
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    after_help = "The GUI starts with the speed, quirk and audio settings of its last session, \
+                  options given here override them. Headless runs use the defaults instead."
+)]
 struct Cli {
-    /// Number of CPU instructions per timer update (timer is at 60Hz)
-    #[arg(short, long, default_value_t = 1)]
-    cycles: u64,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// ROM or source file to load at startup
+    rom: Option<PathBuf>,
+
+    /// Run headless for FRAME frames (60 per second), save the display to OUT and exit
+    #[arg(long, num_args = 2, value_names = ["FRAME", "OUT"])]
+    screenshot_at_frame: Option<Vec<String>>,
+
+    /// Run headless and record the display to OUT (.gif, or .y4m plus .wav), then exit
+    #[arg(long, value_name = "OUT", requires = "frames")]
+    record: Option<PathBuf>,
+
+    /// Number of frames (60 per second) to record
+    #[arg(long)]
+    frames: Option<u64>,
+
+    /// Run headless with the keypad driven by an input movie, using the
+    /// settings it was recorded with. Combines with the screenshot and
+    /// record options, otherwise plays the whole movie and exits.
+    #[arg(long, value_name = "MOVIE")]
+    play_movie: Option<PathBuf>,
+
+    /// CPU speed in instructions per second [default: 700]
+    #[arg(short, long)]
+    frequency: Option<u32>,
+
+    /// Deprecated: instructions per 60 Hz frame, use --frequency instead
+    #[arg(short, long, hide = true, conflicts_with = "frequency")]
+    cycles: Option<u32>,
+
+    /// Run at COSMAC VIP speed using per-instruction cycle costs instead of a
+    /// fixed instruction rate
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    vip_timing: Option<bool>,
+
+    /// Make DXYN wait for the next vertical blank like the COSMAC VIP
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    display_wait: Option<bool>,
 
     /// Enable to mute the beep sound
-    #[arg(short, long, default_value_t = false)]
-    mute: bool,
+    #[arg(short, long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    mute: Option<bool>,
+
+    /// Beep volume from 0 to 1
+    #[arg(long)]
+    volume: Option<f32>,
+
+    /// How to handle questionable operations: strict reports them as errors,
+    /// lenient wraps them like real hardware [default: lenient]
+    #[arg(long)]
+    policy: Option<ExecutionPolicy>,
+}
+
+impl Cli {
+    /// Speed and quirk settings: those given on the command line, the rest
+    /// from `saved`
+    fn profile(&self, saved: MovieSettings) -> MovieSettings {
+        MovieSettings {
+            frequency: self
+                .frequency
+                .or(self.cycles.map(|cycles| cycles.saturating_mul(60)))
+                .unwrap_or(saved.frequency),
+            vip_timing: self.vip_timing.unwrap_or(saved.vip_timing),
+            display_wait: self.display_wait.unwrap_or(saved.display_wait),
+            policy: self.policy.unwrap_or(saved.policy),
+            seed: saved.seed,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a listing of a CHIP-8 ROM with code and sprite data separated
+    Disasm {
+        /// Path to the ROM file
+        rom: PathBuf,
+    },
+    /// Assemble classic or Octo-style CHIP-8 source into a ROM
+    Asm {
+        /// Path to the source file
+        source: PathBuf,
+
+        /// Output ROM path (defaults to the source path with a .ch8 extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Run the ROMs of a regression manifest headlessly and check their display
+    Test {
+        /// Path to the TOML manifest
+        manifest: PathBuf,
+
+        /// Directory for images of the actual display and of mismatches
+        #[arg(short, long, default_value = "regression-output")]
+        output: PathBuf,
+    },
+}
+
+/// Pixel size of scaled screenshots
+const SCREENSHOT_SCALE: usize = 8;
+
+/// How often the loaded ROM is checked for modifications
+const HOT_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+/// File extensions that are assembled before loading
+const SOURCE_EXTENSIONS: [&str; 3] = ["8o", "asm", "src"];
+
+/// Load a ROM, or assemble and load a source file. Returns the program.
+fn load_file(
+    emulator: &mut dyn Emulator<Metadata = Chip8Metadata>,
+    path: &Path,
+) -> Result<Vec<u8>, String> {
+    if is_source(path) {
+        std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| asm::assemble(&source).map_err(|e| e.to_string()))
+            .and_then(|program| {
+                emulator
+                    .load_program(&program)
+                    .map(|_| program)
+                    .map_err(|e| e.to_string())
+            })
+    } else {
+        emulator.load_rom(path).map_err(|e| e.to_string())?;
+        std::fs::read(path).map_err(|e| e.to_string())
+    }
+}
+
+/// Emulator configured from the command line and saved settings, for runs
+/// without a window
+fn headless(cli: &Cli) -> Result<Headless, String> {
+    let rom = cli.rom.as_deref().ok_or("a ROM path is required")?;
+    let settings = Settings::load().unwrap_or_else(|e| {
+        log::warn!("{}, using default settings", e);
+        Settings::default()
+    });
+
+    let movie = cli
+        .play_movie
+        .as_deref()
+        .map(Movie::load)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let movie_settings = movie
+        .as_ref()
+        .map_or(cli.profile(MovieSettings::default()), |movie| {
+            movie.settings
+        });
+
+    let mut emulator = Chip8Emulator::new();
+    emulator.set_mute(true);
+    emulator.set_policy(movie_settings.policy);
+    emulator.set_display_wait(movie_settings.display_wait);
+    emulator.set_seed(movie_settings.seed);
+    emulator.set_palette(settings.palette(emulator.system_name()).colors);
+    let program = load_file(&mut emulator, rom)?;
+    emulator.reset();
+
+    let mut headless = Headless::new(
+        emulator,
+        clock(movie_settings.frequency, movie_settings.vip_timing),
+    );
+    if let Some(movie) = movie {
+        movie
+            .verify(headless.emulator.system_name(), &program)
+            .map_err(|e| e.to_string())?;
+        headless.play(movie);
+    }
+    Ok(headless)
+}
+
+/// CPU clock for the chosen speed settings
+fn clock(frequency: u32, vip_timing: bool) -> Clock {
+    if vip_timing {
+        Clock::Cycles {
+            hz: VIP_CYCLES_PER_SECOND,
+        }
+    } else {
+        Clock::Instructions { hz: frequency }
+    }
+}
+
+fn is_source(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// An input movie being recorded or played back in the GUI
+enum MovieSession {
+    Recording { movie: Movie, path: PathBuf },
+    Playing { movie: Movie, tick: usize },
+}
+
+/// An emulation error that paused execution
+struct Fault {
+    message: String,
+    /// Address of the instruction that failed
+    pc: u16,
+    /// Stack over/underflow, the call trace explains these
+    stack: bool,
 }
 
 pub struct App {
     emulator: Box<dyn Emulator<Metadata = Chip8Metadata>>,
-    cycles: u64,
+    frequency: u32,
+    vip_timing: bool,
+    display_wait: bool,
+    mute: bool,
+    /// Beep volume from 0 to 1
+    volume: f32,
+    scheduler: Scheduler,
+    display_filter: DisplayFilter,
+    /// Timer ticks run since the display was last updated
+    display_ticks: u32,
+    post_process: PostProcess,
+    scaled_screenshot: bool,
+    capture_status: Option<String>,
+    recorder: Option<Recorder>,
+    record_format: RecordFormat,
+    /// The framebuffer was brought up to date (for recording) since the
+    /// texture was last uploaded
+    frame_pending: bool,
+    movie: Option<MovieSession>,
+    settings: Settings,
+    keymap: Keymap,
+    /// SHA-1 of the loaded program, keys ROM-specific settings
+    rom_sha1: Option<String>,
+    show_keymap: bool,
+    /// Button waiting for a key to be bound to it
+    rebinding: Option<usize>,
+    /// Connected controllers, `None` when they cannot be read
+    gamepad: Option<Box<dyn GamepadSource>>,
+    gamepad_map: GamepadMap,
+    /// Controller bindings the ROM database gives the loaded ROM, taking
+    /// precedence over `gamepad_map` without being saved in it
+    rom_gamepad_map: GamepadMap,
+    /// Extra host keys the ROM database gives the loaded ROM
+    rom_keys: Vec<(egui::Key, usize)>,
+    /// Keys held down on the on-screen keypad
+    keypad_held: Vec<bool>,
+    /// Keys tapped on the on-screen keypad, held until a timer tick has run
+    /// with them so that taps shorter than a frame still register
+    keypad_latched: Vec<bool>,
+    show_settings: bool,
+    /// Settings were changed and are saved once the pointer is released
+    settings_dirty: bool,
+    /// Why the settings file could not be used, until dismissed
+    settings_warning: Option<String>,
+    show_library: bool,
+    /// ROMs found by the last library scan, `None` before the first
+    library: Option<Vec<LibraryEntry>>,
+    /// Library scan running in the background
+    library_scan: Option<Receiver<Vec<LibraryEntry>>>,
+    /// Library thumbnails by screenshot path, `None` when unreadable
+    thumbnails: HashMap<PathBuf, Option<egui::TextureHandle>>,
+    /// Speed and quirks for ROMs without settings of their own
+    base_profile: MovieSettings,
+    database: Database,
+    /// Database entry of the loaded ROM
+    rom_info: Option<RomInfo>,
+    /// What the database configured for the loaded ROM
+    auto_config: Option<String>,
+    /// Text of the custom palette colour fields
+    palette_hex: [String; 4],
     texture: Option<egui::TextureHandle>,
-    last_timer_update: Instant,
-    timer_period: Duration,
     memory_scroll_to: Option<usize>,
     is_paused: bool,
     selected_emulator: String,
-    rom_path: Option<std::path::PathBuf>,
+    rom_path: Option<PathBuf>,
+    code_map: Option<CodeMap>,
+    load_error: Option<String>,
+    fault: Option<Fault>,
+    policy: ExecutionPolicy,
+    hot_reload: bool,
+    pause_on_reload: bool,
+    rom_modified: Option<SystemTime>,
+    last_reload_check: Instant,
+    sprite_rows: usize,
+    show_draw_overlay: bool,
+    memory_goto: String,
+    memory_search: String,
+    search_results: Vec<usize>,
+    search_len: usize,
+    memory_baseline: Vec<u8>,
+    value_search: Option<ValueSearch>,
+    value_search_input: String,
 }
 
 impl App {
-    fn new(cycles: u64, mute: bool) -> Self {
+    fn new(cli: &Cli, settings: Settings, settings_warning: Option<String>) -> Self {
+        let profile = cli.profile(settings.profile);
+        let mute = cli.mute.unwrap_or(settings.mute);
+        let volume = cli.volume.unwrap_or(settings.volume).clamp(0.0, 1.0);
         let mut emulator = Chip8Emulator::new();
         emulator.set_mute(mute);
-        
+        emulator.set_volume(volume);
+        emulator.set_display_wait(profile.display_wait);
+        emulator.set_policy(profile.policy);
+        emulator.set_seed(profile.seed);
+        emulator.reset();
+
+        let palette = settings.palette(emulator.system_name());
+        emulator.set_palette(palette.colors);
+        let database = Database::load(Database::user_dir().as_deref()).unwrap_or_else(|e| {
+            log::warn!("{}, using the bundled ROM database", e);
+            Database::bundled().unwrap_or_default()
+        });
+        let keymap = settings.keymap(emulator.system_name(), None, &emulator.keymap());
+        let gamepad_map = settings.gamepad_map(emulator.system_name(), &emulator.gamepad_map());
+        let gamepad = match GilrsSource::new() {
+            Ok(source) => Some(Box::new(source) as Box<dyn GamepadSource>),
+            Err(e) => {
+                log::warn!("Controllers unavailable: {}", e);
+                None
+            }
+        };
+
         Self {
             emulator: Box::new(emulator),
-            cycles,
+            frequency: profile.frequency,
+            vip_timing: profile.vip_timing,
+            display_wait: profile.display_wait,
+            mute,
+            volume,
+            scheduler: Scheduler::new(clock(profile.frequency, profile.vip_timing)),
+            display_filter: DisplayFilter::default(),
+            display_ticks: 0,
+            post_process: PostProcess::default(),
+            scaled_screenshot: false,
+            capture_status: None,
+            recorder: None,
+            record_format: RecordFormat::Gif,
+            frame_pending: false,
+            movie: None,
+            settings,
+            keymap,
+            rom_sha1: None,
+            show_keymap: false,
+            rebinding: None,
+            gamepad,
+            gamepad_map,
+            rom_gamepad_map: GamepadMap::default(),
+            rom_keys: Vec::new(),
+            keypad_held: Vec::new(),
+            keypad_latched: Vec::new(),
+            show_settings: false,
+            settings_dirty: false,
+            settings_warning,
+            show_library: false,
+            library: None,
+            library_scan: None,
+            thumbnails: HashMap::new(),
+            base_profile: profile,
+            database,
+            rom_info: None,
+            auto_config: None,
+            palette_hex: palette.colors.map(palette::to_hex),
             texture: None,
-            last_timer_update: Instant::now(),
-            timer_period: Duration::from_nanos(16_666_667), // ~60Hz
             memory_scroll_to: None,
             is_paused: true,
             selected_emulator: "CHIP-8".to_string(),
             rom_path: None,
+            code_map: None,
+            load_error: None,
+            fault: None,
+            policy: profile.policy,
+            hot_reload: false,
+            pause_on_reload: true,
+            rom_modified: None,
+            last_reload_check: Instant::now(),
+            sprite_rows: 15,
+            show_draw_overlay: false,
+            memory_goto: String::new(),
+            memory_search: String::new(),
+            search_results: Vec::new(),
+            search_len: 0,
+            memory_baseline: Vec::new(),
+            value_search: None,
+            value_search_input: String::new(),
+        }
+    }
+
+    /// Remember memory as it is now, so the viewer can highlight what the
+    /// next run or step changes
+    fn snapshot_memory(&mut self) {
+        self.memory_baseline = self.emulator.memory().to_vec();
+    }
+
+    fn memory_panel(&mut self, ui: &mut egui::Ui) {
+        let metadata = self.emulator.metadata();
+        let memory = self.emulator.memory();
+
+        // Navigation controls
+        ui.horizontal_wrapped(|ui| {
+            if ui.small_button("Program Counter").clicked() {
+                self.memory_scroll_to = Some(metadata.program_counter as usize);
+            }
+            if ui.small_button("Index Register").clicked() {
+                self.memory_scroll_to = Some(metadata.index_register as usize);
+            }
+            if ui.small_button("Program Start").clicked() {
+                self.memory_scroll_to = Some(PROGRAM_START_ADDRESS as usize);
+            }
+            if ui.small_button("Font Start").clicked() {
+                self.memory_scroll_to = Some(FONTSET_START_ADDRESS as usize);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.memory_goto)
+                    .hint_text("Address (hex)")
+                    .desired_width(90.0),
+            );
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.small_button("Go").clicked() || submitted {
+                match usize::from_str_radix(self.memory_goto.trim().trim_start_matches("0x"), 16) {
+                    Ok(addr) if addr < memory.len() => self.memory_scroll_to = Some(addr),
+                    _ => log::warn!("Invalid address: {}", self.memory_goto),
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.memory_search)
+                    .hint_text("Bytes, e.g. A2 ?? 1E")
+                    .desired_width(140.0),
+            );
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.small_button("Find").clicked() || submitted {
+                match memory_search::parse_pattern(&self.memory_search) {
+                    Some(pattern) => {
+                        self.search_results = memory_search::find_all(memory, &pattern);
+                        self.search_len = pattern.len();
+                        if let Some(&first) = self.search_results.first() {
+                            self.memory_scroll_to = Some(first);
+                        }
+                    }
+                    None => {
+                        self.search_results.clear();
+                        log::warn!("Invalid byte pattern: {}", self.memory_search);
+                    }
+                }
+            }
+        });
+
+        if !self.search_results.is_empty() {
+            ui.horizontal_wrapped(|ui| {
+                ui.small(format!("{} match(es):", self.search_results.len()));
+                for &addr in self.search_results.iter().take(16) {
+                    if ui.small_button(format!("{:04X}", addr)).clicked() {
+                        self.memory_scroll_to = Some(addr);
+                    }
+                }
+            });
+        }
+
+        egui::CollapsingHeader::new("Value search")
+            .id_salt("value_search")
+            .show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    if ui.small_button("New search").clicked() {
+                        self.value_search = Some(ValueSearch::new(memory));
+                    }
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.value_search_input)
+                            .hint_text("Value")
+                            .desired_width(40.0),
+                    );
+                });
+
+                let Some(search) = &mut self.value_search else {
+                    ui.small("Start a search, then narrow it down as the value changes");
+                    return;
+                };
+
+                let mut filter = None;
+                ui.horizontal_wrapped(|ui| {
+                    if ui.small_button("= Value").clicked() {
+                        let input = self.value_search_input.trim();
+                        let value = match input.strip_prefix("0x") {
+                            Some(hex) => u8::from_str_radix(hex, 16).ok(),
+                            None => input.parse().ok(),
+                        };
+                        match value {
+                            Some(value) => filter = Some(ValueFilter::Equal(value)),
+                            None => log::warn!("Invalid value: {}", input),
+                        }
+                    }
+                    if ui.small_button("Increased").clicked() {
+                        filter = Some(ValueFilter::Increased);
+                    }
+                    if ui.small_button("Decreased").clicked() {
+                        filter = Some(ValueFilter::Decreased);
+                    }
+                    if ui.small_button("Unchanged").clicked() {
+                        filter = Some(ValueFilter::Unchanged);
+                    }
+                    if ui.small_button("Changed").clicked() {
+                        filter = Some(ValueFilter::Changed);
+                    }
+                });
+                if let Some(filter) = filter {
+                    search.filter(memory, filter);
+                }
+
+                let candidates = search.candidates();
+                ui.small(format!("{} candidate(s)", candidates.len()));
+                ui.horizontal_wrapped(|ui| {
+                    for &addr in candidates.iter().take(32) {
+                        if ui
+                            .small_button(format!("{:04X}={:02X}", addr, memory[addr]))
+                            .clicked()
+                        {
+                            self.memory_scroll_to = Some(addr);
+                        }
+                    }
+                });
+            });
+
+        ui.separator();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let row_spacing = ui.spacing().item_spacing.y;
+        let mut scroll_area = egui::ScrollArea::vertical()
+            .id_salt("memory_scroll")
+            .auto_shrink([false, false]);
+
+        if let Some(scroll_to) = self.memory_scroll_to.take() {
+            let row = scroll_to / 16;
+            scroll_area =
+                scroll_area.vertical_scroll_offset(row as f32 * (row_height + row_spacing));
+        }
+
+        let pc = metadata.program_counter as usize;
+        let index = metadata.index_register as usize;
+        let fault_pc = self.fault.as_ref().map(|fault| fault.pc as usize);
+        let matched = |addr: usize| {
+            let i = self
+                .search_results
+                .partition_point(|&start| start + self.search_len <= addr);
+            self.search_results
+                .get(i)
+                .is_some_and(|&start| start <= addr)
+        };
+
+        scroll_area.show_rows(ui, row_height, memory.len().div_ceil(16), |ui, rows| {
+            ui.style_mut().override_text_style = Some(egui::TextStyle::Monospace);
+
+            for row in rows {
+                let base_addr = row * 16;
+                let chunk = &memory[base_addr..(base_addr + 16).min(memory.len())];
+
+                ui.horizontal(|ui| {
+                    // Address
+                    let addr_color = if (base_addr..base_addr + 16).contains(&pc) {
+                        egui::Color32::YELLOW
+                    } else if (base_addr..base_addr + 16).contains(&index) {
+                        egui::Color32::LIGHT_BLUE
+                    } else {
+                        egui::Color32::GRAY
+                    };
+
+                    ui.colored_label(addr_color, format!("{:04X}:", base_addr));
+
+                    // Hex bytes
+                    for (i, &byte) in chunk.iter().enumerate() {
+                        let byte_addr = base_addr + i;
+                        let changed = self
+                            .memory_baseline
+                            .get(byte_addr)
+                            .is_some_and(|&old| old != byte);
+                        let color = if fault_pc.is_some_and(|f| (f..f + 2).contains(&byte_addr)) {
+                            egui::Color32::RED
+                        } else if byte_addr == pc {
+                            egui::Color32::YELLOW
+                        } else if byte_addr == index {
+                            egui::Color32::LIGHT_BLUE
+                        } else if matched(byte_addr) {
+                            egui::Color32::LIGHT_GREEN
+                        } else if changed {
+                            egui::Color32::from_rgb(255, 140, 0)
+                        } else if byte != 0 {
+                            egui::Color32::WHITE
+                        } else {
+                            egui::Color32::DARK_GRAY
+                        };
+
+                        ui.colored_label(color, format!("{:02X}", byte));
+                    }
+
+                    // ASCII
+                    let ascii: String = chunk
+                        .iter()
+                        .map(|&b| {
+                            if (0x20..0x7F).contains(&b) {
+                                b as char
+                            } else {
+                                '.'
+                            }
+                        })
+                        .collect();
+                    ui.colored_label(egui::Color32::GRAY, ascii);
+                });
+            }
+        });
+    }
+
+    /// Use a palette for the current system and remember the choice
+    fn apply_palette(&mut self, palette: Palette) {
+        self.emulator.set_palette(palette.colors);
+        self.palette_hex = palette.colors.map(palette::to_hex);
+        self.settings
+            .palettes
+            .insert(self.emulator.system_name().to_string(), palette);
+        self.settings_dirty = true;
+    }
+
+    /// Speed and quirk controls
+    fn emulation_controls(&mut self, ui: &mut egui::Ui) {
+        let before = self.profile();
+        // Speed control with slider
+        ui.label("Speed:");
+        let mut changed = ui
+            .add_enabled(
+                !self.vip_timing,
+                egui::Slider::new(&mut self.frequency, 60..=100_000)
+                    .logarithmic(true)
+                    .suffix(" Hz")
+                    .show_value(true),
+            )
+            .changed();
+        changed |= ui
+            .checkbox(&mut self.vip_timing, "COSMAC VIP timing")
+            .changed();
+        if ui
+            .checkbox(&mut self.display_wait, "Display wait")
+            .on_hover_text("DXYN waits for vertical blank, at most 60 sprites per second")
+            .changed()
+        {
+            self.emulator.set_display_wait(self.display_wait);
+            changed = true;
+        }
+        self.scheduler.clock = clock(self.frequency, self.vip_timing);
+
+        ui.horizontal(|ui| {
+            ui.label("Policy:");
+            egui::ComboBox::from_id_salt("policy")
+                .selected_text(self.policy.name())
+                .show_ui(ui, |ui| {
+                    for policy in ExecutionPolicy::ALL {
+                        if ui
+                            .selectable_value(&mut self.policy, policy, policy.name())
+                            .changed()
+                        {
+                            self.emulator.set_policy(policy);
+                            changed = true;
+                        }
+                    }
+                });
+        });
+        if changed {
+            // Only what was changed becomes the default, the rest may have
+            // come from the database or a ROM's own settings
+            let after = self.profile();
+            for default in [&mut self.base_profile, &mut self.settings.profile] {
+                if after.frequency != before.frequency {
+                    default.frequency = after.frequency;
+                }
+                if after.vip_timing != before.vip_timing {
+                    default.vip_timing = after.vip_timing;
+                }
+                if after.display_wait != before.display_wait {
+                    default.display_wait = after.display_wait;
+                }
+                if after.policy != before.policy {
+                    default.policy = after.policy;
+                }
+            }
+            self.auto_config = None;
+            // Remembered for the ROM too, it starts with them next time
+            if let Some(sha1) = &self.rom_sha1 {
+                self.settings
+                    .rom_profiles
+                    .insert(sha1.clone(), self.profile());
+            }
+            self.settings_dirty = true;
+        }
+    }
+
+    fn settings_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_settings;
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                // Keep widget ids apart from the same controls in the side panel
+                ui.push_id("settings_window", |ui| {
+                    ui.heading("Emulation");
+                    ui.add_enabled_ui(self.movie.is_none(), |ui| self.emulation_controls(ui));
+
+                    ui.separator();
+                    ui.heading("Audio");
+                    let mut changed = ui.checkbox(&mut self.mute, "Mute").changed();
+                    changed |= ui
+                        .add_enabled(
+                            !self.mute,
+                            egui::Slider::new(&mut self.volume, 0.0..=1.0).text("Volume"),
+                        )
+                        .changed();
+                    if changed {
+                        self.emulator.set_mute(self.mute);
+                        self.emulator.set_volume(self.volume);
+                        // Saved only from here, so command line overrides
+                        // last for the session
+                        self.settings.mute = self.mute;
+                        self.settings.volume = self.volume;
+                        self.settings_dirty = true;
+                    }
+
+                    ui.separator();
+                    ui.heading("Display");
+                    self.palette_picker(ui);
+
+                    ui.separator();
+                    ui.heading("ROM database");
+                    let response = ui.checkbox(
+                        &mut self.settings.use_database,
+                        "Configure known ROMs automatically",
+                    );
+                    if response.changed() {
+                        self.settings_dirty = true;
+                    }
+                    if let Some(dir) = Database::user_dir() {
+                        response.on_hover_text(format!(
+                            "Speed, quirks and controller keys from the chip-8-database. \
+                             Put its JSON files in {} to use the full database.",
+                            dir.display()
+                        ));
+                    }
+
+                    ui.separator();
+                    ui.heading("Input");
+                    if ui.button("⌨ Key bindings…").clicked() {
+                        self.show_keymap = true;
+                    }
+
+                    ui.separator();
+                    match Settings::path() {
+                        Ok(_) if !self.settings.is_saved() => {
+                            ui.small("Not saved, the settings file could not be read")
+                        }
+                        Ok(path) => ui.small(format!("Saved to {}", path.display())),
+                        Err(e) => ui.small(e.to_string()),
+                    };
+                });
+            });
+        self.show_settings = open;
+    }
+
+    /// Write the settings file. Options given on the command line are not
+    /// part of it unless changed in the UI.
+    fn save_settings(&mut self) {
+        self.settings_dirty = false;
+        if let Err(e) = self.settings.save() {
+            log::warn!("{}", e);
+        }
+    }
+
+    /// What the ROM database says about the loaded ROM
+    fn rom_info_panel(&self, ui: &mut egui::Ui, info: &RomInfo) {
+        let warning = egui::Color32::from_rgb(255, 140, 0);
+        let title = if info.authors.is_empty() {
+            format!("🗂 {}", info.title)
+        } else {
+            format!("🗂 {} by {}", info.title, info.authors.join(", "))
+        };
+        ui.label(title).on_hover_text(format!(
+            "{}, {} instructions per frame",
+            info.platform, info.tickrate
+        ));
+        if let Some(applied) = &self.auto_config {
+            ui.colored_label(
+                egui::Color32::LIGHT_BLUE,
+                format!("⚙ Auto-set: {}", applied),
+            )
+            .on_hover_text(
+                "From the ROM database. Changing the controls keeps your own settings for this ROM",
+            );
         }
+        if !info.supported {
+            ui.colored_label(warning, format!("⚠ Made for {}", info.platform));
+        }
+        let quirks = info.unsupported_quirks();
+        if !quirks.is_empty() {
+            ui.colored_label(
+                warning,
+                format!("⚠ Quirks not emulated: {}", quirks.join(", ")),
+            );
+        }
+        if !info.keys.is_empty() {
+            let keys: Vec<String> = info
+                .keys
+                .iter()
+                .map(|(role, &key)| {
+                    let button = key as usize & 0xF;
+                    let host = self
+                        .rom_keys
+                        .iter()
+                        .find(|&&(_, bound)| bound == button)
+                        .map(|&(host, _)| host)
+                        .or(self.keymap.key(button));
+                    match host {
+                        Some(host) => format!("{} {:X} ({})", role, key, host.name()),
+                        None => format!("{} {:X}", role, key),
+                    }
+                })
+                .collect();
+            ui.small(format!("Keys: {}", keys.join(", ")));
+        }
+    }
+
+    fn library_window(&mut self, ctx: &egui::Context) {
+        if !self.show_library {
+            return;
+        }
+        if self.library.is_none() && self.library_scan.is_none() {
+            self.library_scan = Some(library::scan_in_background(
+                self.settings.library_dirs.clone(),
+            ));
+        }
+        if let Some(scan) = &self.library_scan {
+            match scan.try_recv() {
+                Ok(entries) => {
+                    self.library = Some(entries);
+                    self.library_scan = None;
+                }
+                Err(TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(100)),
+                Err(TryRecvError::Disconnected) => {
+                    log::error!("Library scan failed");
+                    self.library_scan = None;
+                }
+            }
+        }
+
+        let mut open = true;
+        let mut launch = None;
+        egui::Window::new("ROM Library")
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                let mut rescan = false;
+                ui.label("Directories:");
+                let mut removed = None;
+                for (i, dir) in self.settings.library_dirs.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                        ui.label(dir.display().to_string());
+                    });
+                }
+                if let Some(i) = removed {
+                    self.settings.library_dirs.remove(i);
+                    rescan = true;
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("➕ Add directory…").clicked() {
+                        if let Some(dir) = self.file_dialog().pick_folder() {
+                            if !self.settings.library_dirs.contains(&dir) {
+                                self.settings.library_dirs.push(dir);
+                                rescan = true;
+                            }
+                        }
+                    }
+                    if ui.button("🔄 Rescan").clicked() {
+                        self.thumbnails.clear();
+                        rescan = true;
+                    }
+                });
+                if rescan {
+                    self.library_scan = Some(library::scan_in_background(
+                        self.settings.library_dirs.clone(),
+                    ));
+                    self.settings_dirty = true;
+                }
+
+                ui.separator();
+                if self.library_scan.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Scanning…");
+                    });
+                }
+                let entries = self.library.clone().unwrap_or_default();
+                if entries.is_empty() {
+                    if self.library_scan.is_some() {
+                        return;
+                    }
+                    ui.label("No .ch8 or .gb files found, add a directory with ROMs");
+                    return;
+                }
+                ui.small(format!(
+                    "{} ROM(s), double-click one to launch it",
+                    entries.len()
+                ));
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("library_grid")
+                            .num_columns(5)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for entry in &entries {
+                                    let size = egui::vec2(64.0, 32.0);
+                                    match entry
+                                        .thumbnail
+                                        .as_ref()
+                                        .and_then(|path| self.thumbnail(ctx, path))
+                                    {
+                                        Some(texture) => {
+                                            ui.add(
+                                                egui::Image::new(&texture).fit_to_exact_size(size),
+                                            );
+                                        }
+                                        None => {
+                                            ui.allocate_exact_size(size, egui::Sense::hover());
+                                        }
+                                    }
+                                    let selected = self.rom_path.as_ref() == Some(&entry.path);
+                                    if ui
+                                        .selectable_label(selected, &entry.name)
+                                        .on_hover_text(entry.path.display().to_string())
+                                        .double_clicked()
+                                    {
+                                        launch = Some(entry.path.clone());
+                                    }
+                                    ui.label(entry.system);
+                                    ui.label(library::format_size(entry.size));
+                                    ui.monospace(&entry.sha1[..8]).on_hover_text(&entry.sha1);
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+
+        if let Some(path) = launch {
+            self.load_rom(&path);
+            if self.load_error.is_none() {
+                open = false;
+            }
+        }
+        self.show_library = open;
+    }
+
+    /// Texture of a library thumbnail, loaded on first use
+    fn thumbnail(&mut self, ctx: &egui::Context, path: &Path) -> Option<egui::TextureHandle> {
+        self.thumbnails
+            .entry(path.to_path_buf())
+            .or_insert_with(|| match screenshot::load_png(path) {
+                Ok((size, rgba)) => {
+                    let image = egui::ColorImage::from_rgba_unmultiplied(size, &rgba);
+                    let name = path.display().to_string();
+                    Some(ctx.load_texture(name, image, egui::TextureOptions::NEAREST))
+                }
+                Err(e) => {
+                    log::warn!("{}", e);
+                    None
+                }
+            })
+            .clone()
+    }
+
+    /// The session's speed and quirks
+    fn profile(&self) -> MovieSettings {
+        MovieSettings {
+            frequency: self.frequency,
+            vip_timing: self.vip_timing,
+            display_wait: self.display_wait,
+            policy: self.policy,
+            seed: self.emulator.seed(),
+        }
+    }
+
+    /// Use the database's keys for a ROM, and its speed and quirks too with
+    /// `with_profile`. The keys come on top of the saved bindings.
+    fn auto_configure(&mut self, info: &RomInfo, with_profile: bool) {
+        let mut applied = Vec::new();
+        if with_profile {
+            let profile = info.profile(self.base_profile);
+            self.apply_profile(profile);
+            applied.push(format!(
+                "{} Hz ({} per frame)",
+                profile.frequency, info.tickrate
+            ));
+            if let Some(vblank) = info.quirks.vblank {
+                applied.push(format!(
+                    "display wait {}",
+                    if vblank { "on" } else { "off" }
+                ));
+            }
+        }
+
+        self.rom_gamepad_map = GamepadMap::default();
+        for (pad_button, key) in info.gamepad_bindings() {
+            self.rom_gamepad_map.bind(pad_button, key);
+        }
+        // Host keys the user bound elsewhere keep their binding
+        let keymap = &self.keymap;
+        self.rom_keys = info
+            .key_bindings()
+            .into_iter()
+            .filter(|&(host, _)| {
+                (0..keymap.buttons()).all(|button| keymap.key(button) != Some(host))
+            })
+            .collect();
+        if !self.rom_keys.is_empty() {
+            applied.push("arrow keys".to_string());
+        }
+        if !info.gamepad_bindings().is_empty() {
+            applied.push("controller keys".to_string());
+        }
+
+        self.auto_config = (!applied.is_empty()).then(|| applied.join(", "));
+    }
+
+    /// Switch to other speed and quirk settings. The seed takes effect on the
+    /// next reset.
+    fn apply_profile(&mut self, profile: MovieSettings) {
+        self.frequency = profile.frequency;
+        self.vip_timing = profile.vip_timing;
+        self.display_wait = profile.display_wait;
+        self.policy = profile.policy;
+        self.emulator.set_display_wait(profile.display_wait);
+        self.emulator.set_policy(profile.policy);
+        self.emulator.set_seed(profile.seed);
+        self.scheduler.clock = clock(profile.frequency, profile.vip_timing);
+    }
+
+    fn open_rom_dialog(&mut self) {
+        if let Some(path) = self
+            .file_dialog()
+            .add_filter("ROM files", &["ch8", "rom"])
+            .add_filter("CHIP-8 source", &SOURCE_EXTENSIONS)
+            .pick_file()
+        {
+            self.load_rom(&path);
+        }
+    }
+
+    /// File picker opening in the directory of the last ROM
+    fn file_dialog(&self) -> rfd::FileDialog {
+        let dialog = rfd::FileDialog::new();
+        match &self.settings.rom_dir {
+            Some(dir) => dialog.set_directory(dir),
+            None => dialog,
+        }
+    }
+
+    /// Whether the loaded ROM has bindings of its own
+    fn rom_keymap(&self) -> bool {
+        self.rom_sha1
+            .as_ref()
+            .is_some_and(|sha1| self.settings.rom_keymaps.contains_key(sha1))
+    }
+
+    /// Remember the key bindings for the loaded ROM if it has its own,
+    /// otherwise for the current system
+    fn save_keymap(&mut self) {
+        match &self.rom_sha1 {
+            Some(sha1) if self.rom_keymap() => {
+                self.settings
+                    .rom_keymaps
+                    .insert(sha1.clone(), self.keymap.clone());
+            }
+            _ => {
+                self.settings
+                    .keymaps
+                    .insert(self.emulator.system_name().to_string(), self.keymap.clone());
+            }
+        }
+        if let Err(e) = self.settings.save() {
+            log::warn!("{}", e);
+        }
+    }
+
+    fn save_gamepad_map(&mut self) {
+        self.settings.gamepad_maps.insert(
+            self.emulator.system_name().to_string(),
+            self.gamepad_map.clone(),
+        );
+        if let Err(e) = self.settings.save() {
+            log::warn!("{}", e);
+        }
+    }
+
+    /// Controller section of the key bindings window
+    fn gamepad_bindings(&mut self, ui: &mut egui::Ui, names: &[String]) {
+        ui.heading("Controller");
+        match &self.gamepad {
+            None => {
+                ui.small("Controllers are not available on this system");
+                return;
+            }
+            Some(gamepad) => {
+                let connected = gamepad.connected();
+                if connected.is_empty() {
+                    ui.small("No controller connected");
+                }
+                for name in connected {
+                    ui.small(format!("🎮 {}", name));
+                }
+            }
+        }
+
+        let mut changed = false;
+        egui::Grid::new("gamepad_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for pad_button in PadButton::ALL {
+                    let from_database = self.rom_gamepad_map.button(pad_button);
+                    let label = ui.label(pad_button.name());
+                    if from_database.is_some() {
+                        label.on_hover_text("Set by the ROM database for this ROM");
+                    }
+                    let mut bound = from_database.or(self.gamepad_map.button(pad_button));
+                    let mut row_changed = false;
+                    egui::ComboBox::from_id_salt(pad_button.name())
+                        .width(110.0)
+                        .selected_text(
+                            bound
+                                .and_then(|button| names.get(button))
+                                .map_or("—", |name| name),
+                        )
+                        .show_ui(ui, |ui| {
+                            row_changed |= ui.selectable_value(&mut bound, None, "—").changed();
+                            for (button, name) in names.iter().enumerate() {
+                                row_changed |= ui
+                                    .selectable_value(&mut bound, Some(button), name)
+                                    .changed();
+                            }
+                        });
+                    if row_changed {
+                        // The user's choice replaces the database's
+                        self.rom_gamepad_map.unbind(pad_button);
+                        match bound {
+                            Some(button) => self.gamepad_map.bind(pad_button, button),
+                            None => self.gamepad_map.unbind(pad_button),
+                        }
+                        changed = true;
+                    }
+                    ui.end_row();
+                }
+            });
+        if ui.button("Reset controller to defaults").clicked() {
+            self.gamepad_map = GamepadMap::from_defaults(&self.emulator.gamepad_map());
+            self.rom_gamepad_map = GamepadMap::default();
+            changed = true;
+        }
+        if changed {
+            self.save_gamepad_map();
+        }
+    }
+
+    fn keymap_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_keymap;
+        egui::Window::new("Key bindings")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let names = self.emulator.button_names();
+                egui::Grid::new("keymap_grid")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for (button, name) in names.iter().enumerate() {
+                            ui.label(name);
+                            let text = if self.rebinding == Some(button) {
+                                "Press a key…".to_string()
+                            } else {
+                                self.keymap
+                                    .key(button)
+                                    .map_or("—", |key| key.name())
+                                    .to_string()
+                            };
+                            if ui
+                                .add_sized([110.0, 18.0], egui::Button::new(text))
+                                .on_hover_text("Click, then press the new key (Esc cancels)")
+                                .clicked()
+                            {
+                                self.rebinding = Some(button);
+                            }
+                            if ui.small_button("Clear").clicked() {
+                                self.keymap.unbind(button);
+                                self.save_keymap();
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                let mut per_rom = self.rom_keymap();
+                let response = ui
+                    .add_enabled(
+                        self.rom_sha1.is_some(),
+                        egui::Checkbox::new(&mut per_rom, "Only for this ROM"),
+                    )
+                    .on_hover_text("Keep separate bindings for the loaded ROM");
+                if response.changed() {
+                    if let Some(sha1) = self.rom_sha1.clone() {
+                        if per_rom {
+                            self.settings.rom_keymaps.insert(sha1, self.keymap.clone());
+                        } else {
+                            self.settings.rom_keymaps.remove(&sha1);
+                            self.keymap = self.settings.keymap(
+                                self.emulator.system_name(),
+                                None,
+                                &self.emulator.keymap(),
+                            );
+                        }
+                        self.save_keymap();
+                    }
+                }
+                if ui.button("Reset to defaults").clicked() {
+                    self.keymap = Keymap::from_defaults(&self.emulator.keymap());
+                    self.save_keymap();
+                }
+
+                ui.separator();
+                self.gamepad_bindings(ui, &names);
+            });
+        if !open {
+            self.rebinding = None;
+        }
+        self.show_keymap = open;
+    }
+
+    fn palette_picker(&mut self, ui: &mut egui::Ui) {
+        let current = self.settings.palette(self.emulator.system_name());
+
+        let mut selected = None;
+        ui.horizontal(|ui| {
+            ui.label("Palette:");
+            egui::ComboBox::from_id_salt("palette")
+                .selected_text(&current.name)
+                .show_ui(ui, |ui| {
+                    for preset in Palette::presets() {
+                        if ui
+                            .selectable_label(current.name == preset.name, &preset.name)
+                            .clicked()
+                        {
+                            selected = Some(preset);
+                        }
+                    }
+                    if ui
+                        .selectable_label(current.name == palette::CUSTOM, palette::CUSTOM)
+                        .clicked()
+                    {
+                        selected = Some(Palette {
+                            name: palette::CUSTOM.to_string(),
+                            colors: current.colors,
+                        });
+                    }
+                });
+        });
+
+        if current.name == palette::CUSTOM {
+            let mut colors = current.colors;
+            let mut edited = false;
+            egui::Grid::new("palette_grid")
+                .num_columns(3)
+                .show(ui, |ui| {
+                    for (i, label) in ["Background", "Plane 1", "Plane 2", "Both"]
+                        .iter()
+                        .enumerate()
+                    {
+                        ui.label(*label);
+                        if ui
+                            .add(
+                                egui::TextEdit::singleline(&mut self.palette_hex[i])
+                                    .desired_width(70.0),
+                            )
+                            .changed()
+                        {
+                            if let Some(color) = palette::parse_hex(&self.palette_hex[i]) {
+                                colors[i] = color;
+                                edited = true;
+                            }
+                        }
+                        let [_, r, g, b] = colors[i].to_be_bytes();
+                        let (rect, _) =
+                            ui.allocate_exact_size(egui::vec2(14.0, 14.0), egui::Sense::hover());
+                        ui.painter()
+                            .rect_filled(rect, 2.0, egui::Color32::from_rgb(r, g, b));
+                        ui.end_row();
+                    }
+                });
+            if edited {
+                selected = Some(Palette {
+                    name: palette::CUSTOM.to_string(),
+                    colors,
+                });
+            }
+        }
+
+        if let Some(palette) = selected {
+            let edited_text = self.palette_hex.clone();
+            let keep_text = current.name == palette::CUSTOM && palette.name == palette::CUSTOM;
+            self.apply_palette(palette);
+            if keep_text {
+                // Keep what is being typed, e.g. lowercase or without '#'
+                self.palette_hex = edited_text;
+            }
+        }
+    }
+
+    /// Run the instructions and timer ticks due since the last frame
+    /// Returns how many timer ticks ran.
+    fn run_scheduled(&mut self, inputs: &[bool]) -> u32 {
+        self.scheduler.advance(Instant::now());
+
+        let recorder = &mut self.recorder;
+        let frame_pending = &mut self.frame_pending;
+        let session = &mut self.movie;
+        let mut record_error = None;
+        let mut movie_ended = false;
+        let mut ticks = 0;
+        let result = self
+            .scheduler
+            .run_pending(self.emulator.as_mut(), |emulator| {
+                ticks += 1;
+                if let Some(recording) = recorder {
+                    *frame_pending |= emulator.frame_changed();
+                    if let Err(e) = recording.capture(emulator) {
+                        record_error = Some(e);
+                        *recorder = None;
+                    }
+                }
+                match session {
+                    Some(MovieSession::Recording { movie, .. }) => movie.push(inputs),
+                    Some(MovieSession::Playing { movie, tick }) => {
+                        *tick += 1;
+                        match movie.keys(*tick) {
+                            Some(keys) => emulator.set_input_state(&keys),
+                            None => movie_ended = true,
+                        }
+                    }
+                    None => {}
+                }
+                !movie_ended
+            });
+
+        if let Some(e) = record_error {
+            log::error!("{}", e);
+            self.capture_status = Some(e.to_string());
+        }
+        if movie_ended {
+            self.stop_movie();
+            self.is_paused = true;
+        }
+        if let Err(e) = result {
+            self.report_fault(e);
+        }
+        self.display_ticks = self.display_ticks.saturating_add(ticks);
+        ticks
+    }
+
+    /// Reload the ROM and reset with a fresh scheduler, so a movie starts
+    /// from a known state. Returns the program.
+    fn restart_for_movie(&mut self) -> Result<Vec<u8>, String> {
+        let path = self.rom_path.clone().ok_or("No ROM loaded")?;
+        let program = load_file(self.emulator.as_mut(), &path)?;
+        self.emulator.reset();
+        self.scheduler = Scheduler::new(clock(self.frequency, self.vip_timing));
+        self.scheduler.whole_ticks = true;
+        self.snapshot_memory();
+        self.fault = None;
+        self.is_paused = false;
+        Ok(program)
+    }
+
+    fn start_movie_recording(&mut self) {
+        let status = self.restart_for_movie().map(|program| {
+            let settings = self.profile();
+            let path = screenshot::timestamped_path(self.rom_path.as_deref(), movie::EXTENSION);
+            let status = format!("Recording input to {}", path.display());
+            let movie = Movie::new(self.emulator.system_name(), &program, settings);
+            self.movie = Some(MovieSession::Recording { movie, path });
+            status
+        });
+        self.capture_status = Some(status.unwrap_or_else(|e| e));
+    }
+
+    /// Load a movie and play it back against the current ROM
+    fn play_movie(&mut self, path: &Path) {
+        let result = Movie::load(path)
+            .map_err(|e| e.to_string())
+            .and_then(|movie| {
+                self.apply_profile(movie.settings);
+                let program = self.restart_for_movie()?;
+                movie
+                    .verify(self.emulator.system_name(), &program)
+                    .map_err(|e| e.to_string())?;
+                self.emulator
+                    .set_input_state(&movie.keys(0).unwrap_or_default());
+                let ticks = movie.ticks();
+                self.movie = Some(MovieSession::Playing { movie, tick: 0 });
+                Ok(ticks)
+            });
+        self.capture_status = Some(match result {
+            Ok(ticks) => format!("Playing {} ticks from {}", ticks, path.display()),
+            Err(e) => {
+                self.is_paused = true;
+                e
+            }
+        });
+    }
+
+    /// End movie recording or playback, saving a recording
+    fn stop_movie(&mut self) {
+        let status = match self.movie.take() {
+            Some(MovieSession::Recording { movie, path }) => match movie.save(&path) {
+                Ok(()) => format!(
+                    "Recorded {} ticks of input to {}",
+                    movie.ticks(),
+                    path.display()
+                ),
+                Err(e) => e.to_string(),
+            },
+            Some(MovieSession::Playing { movie, tick }) => {
+                format!(
+                    "Played {} of {} ticks",
+                    tick.min(movie.ticks()),
+                    movie.ticks()
+                )
+            }
+            None => return,
+        };
+        self.scheduler.whole_ticks = false;
+        log::info!("{}", status);
+        self.capture_status = Some(status);
+    }
+
+    /// Execute one instruction. Errors pause emulation so the state can be
+    /// inspected; returns whether execution may continue.
+    fn step_emulator(&mut self) -> bool {
+        // While paused the scheduler ticks no timers, so a DXYN waiting for
+        // the display would never finish. Skip the wait, but leave the
+        // timers alone so stepping does not change what the program sees.
+        self.emulator.end_vblank_wait();
+        match self.emulator.step() {
+            Ok(()) => true,
+            Err(e) => {
+                self.report_fault(e);
+                false
+            }
+        }
+    }
+
+    fn report_fault(&mut self, e: EmuError) {
+        log::error!("{}", e);
+        // Save what led up to the fault, playing it back reproduces it
+        self.stop_movie();
+        self.fault = Some(Fault {
+            message: e.to_string(),
+            pc: self.emulator.metadata().program_counter,
+            stack: matches!(e, EmuError::InvalidStackAccess { .. }),
+        });
+        self.is_paused = true;
+    }
+
+    fn toggle_recording(&mut self) {
+        let status = match self.recorder.take() {
+            Some(recorder) => {
+                let path = recorder.path().to_path_buf();
+                match recorder.finish() {
+                    Ok(frames) => format!("Recorded {} frames to {}", frames, path.display()),
+                    Err(e) => e.to_string(),
+                }
+            }
+            None => {
+                let (width, height) = self.emulator.resolution();
+                let path = screenshot::timestamped_path(
+                    self.rom_path.as_deref(),
+                    self.record_format.extension(),
+                );
+                let scale = if self.scaled_screenshot {
+                    SCREENSHOT_SCALE
+                } else {
+                    1
+                };
+                match Recorder::create(&path, width, height, scale) {
+                    Ok(recorder) => {
+                        self.recorder = Some(recorder);
+                        format!("Recording to {}", path.display())
+                    }
+                    Err(e) => e.to_string(),
+                }
+            }
+        };
+        log::info!("{}", status);
+        self.capture_status = Some(status);
+    }
+
+    /// Save the display as a PNG next to the ROM
+    fn screenshot(&mut self) {
+        let (width, height) = self.emulator.resolution();
+        let path = screenshot::timestamped_path(self.rom_path.as_deref(), "png");
+        let scale = if self.scaled_screenshot {
+            SCREENSHOT_SCALE
+        } else {
+            1
+        };
+        self.emulator.frame_changed();
+        self.capture_status = Some(
+            match screenshot::save_png(&path, self.emulator.framebuffer(), width, height, scale) {
+                Ok(()) => {
+                    log::info!("Saved screenshot {:?}", path);
+                    format!("Saved {}", path.display())
+                }
+                Err(e) => {
+                    log::error!("{}", e);
+                    e.to_string()
+                }
+            },
+        );
+    }
+
+    /// Label of an address if the analysis found one, its hex value otherwise
+    fn symbol(&self, addr: u16) -> String {
+        self.code_map
+            .as_ref()
+            .and_then(|map| map.label(addr))
+            .unwrap_or_else(|| format!("0x{:03X}", addr))
+    }
+
+    fn call_stack(&self, ui: &mut egui::Ui, metadata: &Chip8Metadata) {
+        ui.style_mut().override_text_style = Some(egui::TextStyle::Monospace);
+
+        if metadata.stack_pointer == 0 {
+            ui.label("Empty");
+        }
+
+        // Each entry is a return address, the CALL sits right before it
+        let depth = (metadata.stack_pointer as usize).min(metadata.stack.len());
+        for i in (0..depth).rev() {
+            let returns_to = metadata.stack[i];
+            let called_from = returns_to.wrapping_sub(2);
+            let opcode = metadata
+                .memory
+                .get(called_from as usize..called_from as usize + 2)
+                .map(|bytes| (bytes[0] as u16) << 8 | bytes[1] as u16)
+                .unwrap_or(0);
+            let subroutine = if opcode & 0xF000 == 0x2000 {
+                self.symbol(opcode & 0x0FFF)
+            } else {
+                "???".to_string()
+            };
+
+            let color = if i == depth - 1 {
+                egui::Color32::YELLOW
+            } else {
+                egui::Color32::WHITE
+            };
+            ui.colored_label(color, format!("[{}] {}", i, subroutine));
+            ui.colored_label(
+                egui::Color32::GRAY,
+                format!(
+                    "    from {:03X}, returns to {:03X}",
+                    called_from, returns_to
+                ),
+            );
+        }
+
+        let trace = |ui: &mut egui::Ui| {
+            if metadata.call_trace.is_empty() {
+                ui.label("No calls yet");
+            }
+            for event in metadata.call_trace.iter().rev() {
+                let text = match *event {
+                    StackEvent::Call { from, target } => {
+                        format!("{:03X}: CALL {}", from, self.symbol(target))
+                    }
+                    StackEvent::Return { from, to } => format!("{:03X}: RET -> {:03X}", from, to),
+                };
+                ui.label(text);
+            }
+        };
+
+        if let Some(fault) = self.fault.as_ref().filter(|fault| fault.stack) {
+            ui.colored_label(egui::Color32::RED, &fault.message);
+            ui.label("Last calls and returns, newest first:");
+            trace(ui);
+        } else {
+            egui::CollapsingHeader::new("Recent calls")
+                .id_salt("call_trace")
+                .show(ui, trace);
+        }
+
+        ui.style_mut().override_text_style = None;
+    }
+
+    /// Preview the bytes at I as an 8 pixel wide sprite
+    fn sprite_inspector(&mut self, ui: &mut egui::Ui, metadata: &Chip8Metadata) {
+        ui.add(egui::Slider::new(&mut self.sprite_rows, 1..=32).text("rows"));
+        ui.checkbox(&mut self.show_draw_overlay, "Show last draw on display");
+
+        let cell = ((ui.available_width() - 90.0) / 8.0).clamp(4.0, 12.0);
+        let memory_len = metadata.memory.len();
+
+        ui.style_mut().override_text_style = Some(egui::TextStyle::Monospace);
+        for row in 0..self.sprite_rows {
+            let addr = (metadata.index_register as usize + row) % memory_len;
+            let byte = metadata.memory[addr];
+
+            ui.horizontal(|ui| {
+                ui.label(format!("{:03X}: {:02X}", addr, byte));
+
+                let (rect, _) =
+                    ui.allocate_exact_size(egui::vec2(cell * 8.0, cell), egui::Sense::hover());
+                let painter = ui.painter();
+                painter.rect_filled(rect, 0.0, egui::Color32::BLACK);
+                for bit in 0..8 {
+                    if byte & (0x80 >> bit) != 0 {
+                        let min = rect.min + egui::vec2(bit as f32 * cell, 0.0);
+                        painter.rect_filled(
+                            egui::Rect::from_min_size(min, egui::vec2(cell, cell)).shrink(0.5),
+                            0.0,
+                            egui::Color32::WHITE,
+                        );
+                    }
+                }
+            });
+        }
+        ui.style_mut().override_text_style = None;
+
+        if let Some(draw) = &metadata.last_draw {
+            ui.small(format!(
+                "Last draw: 8x{} at ({}, {}), {} collision(s)",
+                draw.height,
+                draw.x,
+                draw.y,
+                draw.collisions.len()
+            ));
+        }
+    }
+
+    /// Outline the last sprite drawn and mark the pixels that collided
+    fn draw_overlay(painter: &egui::Painter, display: egui::Rect, scale: f32, draw: &SpriteDraw) {
+        let pixel = |x: usize, y: usize, w: usize, h: usize| {
+            egui::Rect::from_min_size(
+                display.min + egui::vec2(x as f32, y as f32) * scale,
+                egui::vec2(w as f32, h as f32) * scale,
+            )
+        };
+
+        // The sprite wraps around the screen edges, so the box may be split
+        let split = |start: usize, len: usize, size: usize| {
+            let first = len.min(size - start);
+            let mut parts = vec![(start, first)];
+            if first < len {
+                parts.push((0, len - first));
+            }
+            parts
+        };
+
+        let stroke = egui::Stroke::new(1.5, egui::Color32::from_rgb(0, 200, 255));
+        for (x, w) in split(draw.x, 8, WIDTH) {
+            for (y, h) in split(draw.y, draw.height, HEIGHT) {
+                painter.rect_stroke(pixel(x, y, w, h), 0.0, stroke, egui::StrokeKind::Inside);
+            }
+        }
+
+        for &(x, y) in &draw.collisions {
+            painter.rect_filled(
+                pixel(x, y, 1, 1),
+                0.0,
+                egui::Color32::from_rgba_unmultiplied(255, 0, 0, 160),
+            );
+        }
+    }
+
+    fn modified_time(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Reload the ROM (or re-assemble its source) when the file changed on disk
+    fn check_hot_reload(&mut self) {
+        if !self.hot_reload || self.last_reload_check.elapsed() < HOT_RELOAD_INTERVAL {
+            return;
+        }
+        self.last_reload_check = Instant::now();
+
+        let Some(path) = self.rom_path.clone() else {
+            return;
+        };
+        let modified = Self::modified_time(&path);
+        if modified.is_none() || modified == self.rom_modified {
+            return;
+        }
+
+        log::info!("ROM changed on disk, reloading: {:?}", path);
+        // Remember the timestamp even if loading fails, so a broken build is
+        // not retried every poll until the file changes again
+        self.rom_modified = modified;
+        self.load_rom(&path);
+        if self.pause_on_reload {
+            self.is_paused = true;
+        }
+    }
+
+    /// Load a ROM, assembling it first if it is a source file
+    fn load_rom(&mut self, path: &Path) {
+        self.stop_movie();
+        if let Some(system) = library::system_for(path) {
+            if system != self.emulator.system_name() {
+                self.load_error = Some(format!(
+                    "{} needs the {} core, which is not available yet",
+                    path.display(),
+                    system
+                ));
+                return;
+            }
+        }
+        match load_file(self.emulator.as_mut(), path) {
+            Ok(program) => {
+                self.analyze_program(program.len());
+                self.snapshot_memory();
+                let sha1 = movie::sha1(&program);
+                self.keymap = self.settings.keymap(
+                    self.emulator.system_name(),
+                    Some(&sha1),
+                    &self.emulator.keymap(),
+                );
+                self.rom_info = self.database.lookup(&sha1);
+                self.auto_config = None;
+                self.rom_gamepad_map = GamepadMap::default();
+                self.rom_keys.clear();
+                // Speed and quirks chosen for the ROM win over the database
+                let saved = self.settings.rom_profiles.get(&sha1).copied();
+                match self.rom_info.clone() {
+                    Some(info) if self.settings.use_database => {
+                        if let Some(profile) = saved {
+                            self.apply_profile(profile);
+                        }
+                        self.auto_configure(&info, saved.is_none());
+                    }
+                    _ => self.apply_profile(saved.unwrap_or(self.base_profile)),
+                }
+                self.rom_sha1 = Some(sha1);
+                self.settings.add_recent(path);
+                self.settings_dirty = true;
+                self.rom_modified = Self::modified_time(path);
+                self.rom_path = Some(path.to_path_buf());
+                self.settings.rom_dir = path.parent().map(Path::to_path_buf);
+                self.load_error = None;
+                self.fault = None;
+                self.is_paused = false;
+                log::info!("Loaded ROM: {:?}", path);
+            }
+            Err(e) => {
+                log::error!("Failed to load ROM: {}", e);
+                self.load_error = Some(e);
+            }
+        }
+    }
+
+    /// Run the static analysis over the freshly loaded program
+    fn analyze_program(&mut self, size: usize) {
+        let start = PROGRAM_START_ADDRESS;
+        let metadata = self.emulator.metadata();
+        self.code_map = Some(CodeMap::analyze(
+            &metadata.memory,
+            start,
+            start.saturating_add(size as u16),
+        ));
     }
 
     fn update_texture(&mut self, ctx: &egui::Context) {
+        let changed = self.emulator.frame_changed() || std::mem::take(&mut self.frame_pending);
+        let ticks = std::mem::take(&mut self.display_ticks);
+        // Fades follow emulated time, so they hold still while paused
+        let fading = ticks > 0 && self.display_filter.is_fading();
+        if !changed && !fading && self.texture.is_some() {
+            return;
+        }
+
         let (width, height) = self.emulator.resolution();
-        let framebuffer = self.emulator.framebuffer();
+        let framebuffer = self
+            .display_filter
+            .apply(self.emulator.framebuffer(), ticks);
+        let processed = self.post_process.apply(framebuffer, width, height);
 
-        let rgba: Vec<u8> = framebuffer
+        let rgba: Vec<u8> = processed
+            .pixels
             .iter()
             .flat_map(|&argb| {
                 let a = ((argb >> 24) & 0xFF) as u8;
@@ -66,16 +1856,22 @@ impl App {
             })
             .collect();
 
-        let size = [width, height];
+        let size = [processed.width, processed.height];
         let image = egui::ColorImage::from_rgba_unmultiplied(size, &rgba);
+        // Effects are already upscaled, smooth the rest of the way
+        let options = if self.post_process.is_active() {
+            egui::TextureOptions::LINEAR
+        } else {
+            egui::TextureOptions::NEAREST
+        };
 
         if let Some(tex) = &mut self.texture {
-            tex.set(image, egui::TextureOptions::NEAREST);
+            tex.set(image, options);
         } else {
             self.texture = Some(ctx.load_texture(
-                &format!("{}_screen", self.emulator.system_name()),
+                format!("{}_screen", self.emulator.system_name()),
                 image,
-                egui::TextureOptions::NEAREST,
+                options,
             ));
         }
     }
@@ -87,57 +1883,77 @@ impl eframe::App for App {
         // OS complaining about application not responding.
         if !self.is_paused {
             ctx.request_repaint_after(std::time::Duration::from_millis(16));
+        } else if self.hot_reload {
+            // Keep polling the ROM file while paused
+            ctx.request_repaint_after(HOT_RELOAD_INTERVAL);
         } else {
             ctx.request_repaint_after(std::time::Duration::from_secs(1000));
         }
 
+        // Controllers do not wake the UI, poll them while one is connected
+        if self
+            .gamepad
+            .as_ref()
+            .is_some_and(|gamepad| !gamepad.connected().is_empty())
+        {
+            ctx.request_repaint_after(std::time::Duration::from_millis(16));
+        }
+
+        self.check_hot_reload();
+
         // --- Keyboard input ---
-        let mut inputs = [false; 16];
-        let keymap = self.emulator.keymap();
-        ctx.input(|i| {
-            for (chip8_key, key_str) in &keymap {
-                let key = match key_str.as_str() {
-                    "X" => egui::Key::X,
-                    "1" => egui::Key::Num1,
-                    "2" => egui::Key::Num2,
-                    "3" => egui::Key::Num3,
-                    "4" => egui::Key::Num4,
-                    "Q" => egui::Key::Q,
-                    "W" => egui::Key::W,
-                    "E" => egui::Key::E,
-                    "R" => egui::Key::R,
-                    "A" => egui::Key::A,
-                    "S" => egui::Key::S,
-                    "D" => egui::Key::D,
-                    "F" => egui::Key::F,
-                    "Z" => egui::Key::Z,
-                    "C" => egui::Key::C,
-                    "V" => egui::Key::V,
-                    _ => continue,
-                };
-                inputs[*chip8_key] = i.key_down(key);
+        if let Some(button) = self.rebinding {
+            let key = ctx.input(|i| {
+                i.events.iter().find_map(|event| match event {
+                    egui::Event::Key {
+                        key, pressed: true, ..
+                    } => Some(*key),
+                    _ => None,
+                })
+            });
+            match key {
+                Some(egui::Key::Escape) => self.rebinding = None,
+                Some(key) => {
+                    self.keymap.bind(button, key);
+                    self.rebinding = None;
+                    self.save_keymap();
+                }
+                None => {}
             }
+        }
+        let mut inputs = ctx.input(|i| {
+            let mut inputs = self.keymap.pressed(i);
+            for &(key, button) in &self.rom_keys {
+                if let Some(input) = inputs.get_mut(button) {
+                    *input |= i.key_down(key);
+                }
+            }
+            inputs
         });
-        self.emulator.set_input_state(&inputs);
+        if let Some(gamepad) = &mut self.gamepad {
+            self.gamepad_map
+                .with_overrides(&self.rom_gamepad_map)
+                .apply(&gamepad.poll(), &mut inputs);
+        }
+        for (input, &held) in inputs.iter_mut().zip(&self.keypad_held) {
+            *input |= held;
+        }
+        for (input, &latched) in inputs.iter_mut().zip(&self.keypad_latched) {
+            *input |= latched;
+        }
+        if !matches!(self.movie, Some(MovieSession::Playing { .. })) {
+            self.emulator.set_input_state(&inputs);
+        }
 
-        // --- Timers ---
-        if !self.is_paused {
-            let now = Instant::now();
-            let elapsed = now.duration_since(self.last_timer_update);
-            if elapsed >= self.timer_period {
-                self.emulator.update_timers(elapsed);
-                self.last_timer_update = now;
-            }
+        if ctx.input(|i| i.key_pressed(egui::Key::F12)) {
+            self.screenshot();
         }
 
-        // --- Execute instructions (only if not paused) ---
-        if !self.is_paused {
-            for _ in 0..self.cycles {
-                self.emulator.step().unwrap_or_else(|e| {
-                    log::error!("{}", e);
-                    std::process::exit(1);
-                });
-            }
+        // --- Execute instructions and tick timers (only if not paused) ---
+        if self.is_paused {
+            self.scheduler.reset();
+        } else if self.run_scheduled(&inputs) > 0 {
+            self.keypad_latched.clear();
         }
 
         // --- Redraw display if needed ---
@@ -150,10 +1966,108 @@ impl eframe::App for App {
         // Make panels wider to take more space from display
         let panel_width = (available_width * 0.25).clamp(200.0, 350.0);
 
+        // --- TOP PANEL: Menu bar ---
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::MenuBar::new().ui(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("📁 Open ROM…").clicked() {
+                        ui.close();
+                        self.open_rom_dialog();
+                    }
+                    ui.menu_button("Recent", |ui| {
+                        if self.settings.recent_roms.is_empty() {
+                            ui.label("No recent ROMs");
+                        }
+                        for path in self.settings.recent_roms.clone() {
+                            let name = path.file_name().unwrap_or_default().to_string_lossy();
+                            if ui
+                                .add_enabled(path.exists(), egui::Button::new(name))
+                                .on_hover_text(path.display().to_string())
+                                .clicked()
+                            {
+                                ui.close();
+                                self.load_rom(&path);
+                            }
+                        }
+                        ui.separator();
+                        if ui.button("Clear").clicked() {
+                            self.settings.recent_roms.clear();
+                            self.settings_dirty = true;
+                        }
+                    });
+                    if ui.button("📚 Library…").clicked() {
+                        ui.close();
+                        self.show_library = true;
+                    }
+                });
+                ui.menu_button("View", |ui| {
+                    let before = self.post_process;
+                    ui.checkbox(&mut self.post_process.integer_scale, "Integer scaling");
+                    ui.separator();
+                    ui.checkbox(&mut self.post_process.scanlines, "Scanlines");
+                    ui.checkbox(&mut self.post_process.pixel_grid, "Pixel grid");
+                    ui.checkbox(&mut self.post_process.curvature, "CRT curvature");
+                    ui.checkbox(&mut self.post_process.bloom, "Bloom");
+                    if self.post_process != before {
+                        // Re-upload so the change shows while paused
+                        self.texture = None;
+                    }
+                });
+                if ui.button("⚙ Settings").clicked() {
+                    self.show_settings = true;
+                }
+                ui.menu_button("Input", |ui| {
+                    if ui.button("Key bindings…").clicked() {
+                        self.show_keymap = true;
+                        ui.close();
+                    }
+                });
+            });
+        });
+        self.keymap_window(ctx);
+        self.settings_window(ctx);
+        self.library_window(ctx);
+
+        // --- TOP PANEL: Error banner ---
+        if let Some(fault) = &self.fault {
+            let message = fault.message.clone();
+            egui::TopBottomPanel::top("error_banner").show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        format!("⚠ Emulation paused: {}", message),
+                    );
+
+                    if ui.button("⏭ Skip instruction").clicked() {
+                        self.emulator.skip_instruction();
+                        self.fault = None;
+                    }
+                    if ui.button("⏹ Reset").clicked() {
+                        self.emulator.reset();
+                        self.fault = None;
+                    }
+                    if self.policy == ExecutionPolicy::Strict
+                        && ui
+                            .button("▶ Continue leniently")
+                            .on_hover_text(
+                                "Switch to the lenient policy and run the instruction again",
+                            )
+                            .clicked()
+                    {
+                        self.policy = ExecutionPolicy::Lenient;
+                        self.emulator.set_policy(self.policy);
+                        self.snapshot_memory();
+                        self.fault = None;
+                        self.is_paused = false;
+                    }
+                });
+            });
+        }
+
         // --- LEFT PANEL: Registers + Keys + Controls + Stack ---
-        egui::SidePanel::left("left_panel")
+        let left_panel = egui::SidePanel::left("left_panel")
             .resizable(true)
-            .default_width(panel_width)
+            .default_width(self.settings.layout.left_panel.unwrap_or(panel_width))
             .width_range(0.0..=220.0)
             .show(ctx, |ui| {
                 egui::ScrollArea::vertical().show(ui, |ui| {
@@ -175,26 +2089,38 @@ impl eframe::App for App {
 
                     // ROM file selector
                     ui.heading("ROM File");
-                    if ui.button("📁 Load ROM").clicked() {
-                        if let Some(path) = rfd::FileDialog::new()
-                            .add_filter("ROM files", &["ch8", "rom"])
-                            .pick_file()
-                        {
-                            match self.emulator.load_rom(&path) {
-                                Ok(_) => {
-                                    self.rom_path = Some(path.clone());
-                                    self.is_paused = false;
-                                    log::info!("Loaded ROM: {:?}", path);
-                                }
-                                Err(e) => {
-                                    log::error!("Failed to load ROM: {}", e);
-                                }
-                            }
+                    ui.horizontal(|ui| {
+                        if ui.button("📁 Load ROM").clicked() {
+                            self.open_rom_dialog();
                         }
-                    }
+                        if ui.button("📚 Library").clicked() {
+                            self.show_library = true;
+                        }
+                    });
                     if let Some(path) = &self.rom_path {
                         ui.label(format!("📄 {}", path.file_name().unwrap_or_default().to_string_lossy()));
                     }
+                    if let Some(info) = self.rom_info.clone() {
+                        self.rom_info_panel(ui, &info);
+                    }
+                    if let Some(error) = &self.load_error {
+                        ui.colored_label(egui::Color32::RED, error);
+                    }
+                    if let Some(warning) = &self.settings_warning {
+                        let mut dismissed = false;
+                        ui.horizontal_wrapped(|ui| {
+                            ui.colored_label(egui::Color32::from_rgb(255, 140, 0), warning);
+                            dismissed = ui.small_button("✖").clicked();
+                        });
+                        if dismissed {
+                            self.settings_warning = None;
+                        }
+                    }
+                    ui.checkbox(&mut self.hot_reload, "🔄 Reload on change");
+                    ui.add_enabled(
+                        self.hot_reload,
+                        egui::Checkbox::new(&mut self.pause_on_reload, "Pause after reload"),
+                    );
 
                     ui.separator();
 
@@ -209,27 +2135,146 @@ impl eframe::App for App {
                             })
                             .clicked()
                         {
+                            if self.is_paused {
+                                self.snapshot_memory();
+                                self.fault = None;
+                            }
                             self.is_paused = !self.is_paused;
                         }
-                        if ui.button("⏹ Reset").clicked() {
+                        // Single steps and resets are not part of a movie
+                        let movie_active = self.movie.is_some();
+                        if ui
+                            .add_enabled(
+                                self.is_paused && !movie_active,
+                                egui::Button::new("⏭ Step"),
+                            )
+                            .clicked()
+                        {
+                            self.snapshot_memory();
+                            self.step_emulator();
+                        }
+                        if ui
+                            .add_enabled(!movie_active, egui::Button::new("⏹ Reset"))
+                            .clicked()
+                        {
                             self.emulator.reset();
+                            self.fault = None;
                             self.is_paused = true;
                         }
                     });
+                    ui.horizontal(|ui| {
+                        if ui.button("📷 Screenshot").on_hover_text("F12").clicked() {
+                            self.screenshot();
+                        }
+                        ui.checkbox(&mut self.scaled_screenshot, "Scaled");
+                    });
+                    ui.horizontal(|ui| {
+                        let recording = self.recorder.is_some();
+                        if ui
+                            .button(if recording {
+                                "⏹ Stop recording"
+                            } else {
+                                "⏺ Record"
+                            })
+                            .clicked()
+                        {
+                            self.toggle_recording();
+                        }
+                        ui.add_enabled_ui(!recording, |ui| {
+                            egui::ComboBox::from_id_salt("record_format")
+                                .selected_text(self.record_format.name())
+                                .show_ui(ui, |ui| {
+                                    for format in RecordFormat::ALL {
+                                        ui.selectable_value(
+                                            &mut self.record_format,
+                                            format,
+                                            format.name(),
+                                        );
+                                    }
+                                });
+                        });
+                    });
+                    if let Some(recorder) = &self.recorder {
+                        ui.small(format!("{} frames", recorder.frames()));
+                    }
+                    ui.horizontal(|ui| match &self.movie {
+                        Some(session) => {
+                            let (label, progress) = match session {
+                                MovieSession::Recording { movie, .. } => {
+                                    ("⏹ Stop input recording", format!("{} ticks", movie.ticks()))
+                                }
+                                MovieSession::Playing { movie, tick } => {
+                                    ("⏹ Stop playback", format!("{}/{}", tick, movie.ticks()))
+                                }
+                            };
+                            if ui.button(label).clicked() {
+                                self.stop_movie();
+                            }
+                            ui.small(progress);
+                        }
+                        None => {
+                            let has_rom = self.rom_path.is_some();
+                            if ui
+                                .add_enabled(has_rom, egui::Button::new("⏺ Record input"))
+                                .on_hover_text("Restart the ROM and record the keypad to a movie")
+                                .clicked()
+                            {
+                                self.start_movie_recording();
+                            }
+                            if ui
+                                .add_enabled(has_rom, egui::Button::new("▶ Play movie"))
+                                .clicked()
+                            {
+                                if let Some(path) = self
+                                    .file_dialog()
+                                    .add_filter("Input movie", &[movie::EXTENSION])
+                                    .pick_file()
+                                {
+                                    self.play_movie(&path);
+                                }
+                            }
+                        }
+                    });
+                    if let Some(status) = &self.capture_status {
+                        ui.small(status);
+                    }
 
                     ui.add_space(8.0);
 
-                    // Speed control with slider
-                    ui.label("Speed:");
-                    let mut speed = self.cycles as f32;
-                    ui.add(
-                        egui::Slider::new(&mut speed, 1.0..=1000.0)
-                            .logarithmic(true)
-                            .text("cycles")
-                            .show_value(true),
-                    );
-                    self.cycles = speed as u64;
-                    ui.small(format!("{}x speed", self.cycles));
+                    // Settings are fixed while a movie is recorded or played
+                    ui.add_enabled_ui(self.movie.is_none(), |ui| self.emulation_controls(ui));
+                    let fixups = self.emulator.metadata().lenient_fixups;
+                    if fixups > 0 {
+                        ui.small(format!("{} lenient fixup(s)", fixups));
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Filter:");
+                        egui::ComboBox::from_id_salt("display_filter")
+                            .selected_text(self.display_filter.mode.name())
+                            .show_ui(ui, |ui| {
+                                for mode in FilterMode::ALL {
+                                    if ui
+                                        .selectable_value(
+                                            &mut self.display_filter.mode,
+                                            mode,
+                                            mode.name(),
+                                        )
+                                        .changed()
+                                    {
+                                        // Re-upload so the change shows while paused
+                                        self.texture = None;
+                                    }
+                                }
+                            });
+                    });
+                    if self.display_filter.mode == FilterMode::Phosphor {
+                        ui.add(
+                            egui::Slider::new(&mut self.display_filter.decay, 0.0..=0.95)
+                                .text("decay"),
+                        );
+                    }
+                    self.palette_picker(ui);
 
                     ui.separator();
 
@@ -296,7 +2341,7 @@ impl eframe::App for App {
                     ui.separator();
                     ui.heading("Keys");
 
-                    // Compact keypad
+                    // COSMAC VIP keypad, click or touch to press
                     let keys = [
                         [0x1, 0x2, 0x3, 0xC],
                         [0x4, 0x5, 0x6, 0xD],
@@ -306,133 +2351,87 @@ impl eframe::App for App {
 
                     let button_size = ((ui.available_width() - 24.0) / 4.0).clamp(20.0, 35.0);
 
+                    let mut keypad_held = vec![false; inputs.len()];
                     for row in keys {
                         ui.horizontal(|ui| {
                             for &k in &row {
-                                let pressed = inputs[k];
-                                let button = egui::Button::new(format!("{:X}", k))
+                                let pressed = inputs.get(k).copied().unwrap_or(false);
+                                let key = self.keymap.key(k).map(|key| key.name());
+                                // The digit with the bound host key below it
+                                let mut label = egui::text::LayoutJob::default();
+                                label.append(
+                                    &format!("{:X}", k),
+                                    0.0,
+                                    egui::TextFormat {
+                                        font_id: egui::FontId::monospace(14.0),
+                                        color: egui::Color32::PLACEHOLDER,
+                                        ..Default::default()
+                                    },
+                                );
+                                if let Some(key) = key {
+                                    label.append(
+                                        &format!("\n{}", key),
+                                        0.0,
+                                        egui::TextFormat {
+                                            font_id: egui::FontId::proportional(8.0),
+                                            color: egui::Color32::GRAY,
+                                            ..Default::default()
+                                        },
+                                    );
+                                }
+                                let mut button = egui::Button::new(label)
                                     .min_size(egui::vec2(button_size, button_size));
                                 if pressed {
-                                    ui.add_sized(
-                                        [button_size, button_size],
-                                        button.fill(egui::Color32::GREEN),
-                                    );
-                                } else {
-                                    ui.add_sized([button_size, button_size], button);
+                                    button = button.fill(egui::Color32::GREEN);
+                                }
+                                let response = ui
+                                    .add_sized([button_size, button_size], button)
+                                    .on_hover_text(key.unwrap_or("unbound"));
+                                if let Some(held) = keypad_held.get_mut(k) {
+                                    *held = response.is_pointer_button_down_on();
+                                }
+                                if response.is_pointer_button_down_on() || response.clicked() {
+                                    self.keypad_latched.resize(inputs.len(), false);
+                                    if let Some(latched) = self.keypad_latched.get_mut(k) {
+                                        *latched = true;
+                                    }
+                                    // Apply it right away instead of on the
+                                    // next repaint
+                                    ui.ctx().request_repaint();
                                 }
                             }
                         });
                     }
+                    self.keypad_held = keypad_held;
+                    if ui.small_button("⌨ Key bindings…").clicked() {
+                        self.show_keymap = true;
+                    }
 
                     // --- STACK SECTION ---
                     ui.separator();
                     ui.heading("Stack");
 
                     let metadata = self.emulator.metadata();
-                    if metadata.stack_pointer == 0 {
-                        ui.label("Empty");
-                    } else {
-                        // Stack visualization as a vertical list for better readability
-                        ui.style_mut().override_text_style = Some(egui::TextStyle::Monospace);
-
-                        for i in (0..metadata.stack_pointer).rev() {
-                            if (i as usize) < metadata.stack.len() {
-                                let color = if i == metadata.stack_pointer - 1 {
-                                    egui::Color32::YELLOW
-                                } else {
-                                    egui::Color32::WHITE
-                                };
-                                ui.horizontal(|ui| {
-                                    ui.colored_label(color, format!("[{}]", i));
-                                    ui.colored_label(
-                                        color,
-                                        format!("0x{:04X}", metadata.stack[i as usize]),
-                                    );
-                                });
-                            }
-                        }
+                    self.call_stack(ui, &metadata);
 
-                        ui.style_mut().override_text_style = None; // Reset text style
-                    }
+                    // --- SPRITE SECTION ---
+                    ui.separator();
+                    ui.heading("Sprite");
+                    self.sprite_inspector(ui, &metadata);
                 });
             });
+        self.settings.layout.left_panel = Some(left_panel.response.rect.width());
 
         // --- RIGHT PANEL: Memory Viewer ---
-        egui::SidePanel::right("right_panel")
+        let right_panel = egui::SidePanel::right("right_panel")
             .resizable(true)
-            .default_width(panel_width)
-            .width_range(0.0..=420.0)
+            .default_width(self.settings.layout.right_panel.unwrap_or(panel_width))
+            .width_range(0.0..=520.0)
             .show(ctx, |ui| {
                 ui.heading("Memory");
-
-                let metadata = self.emulator.metadata();
-
-                // Navigation controls
-                ui.horizontal_wrapped(|ui| {
-                    if ui.small_button("Program Counter").clicked() {
-                        self.memory_scroll_to = Some(metadata.program_counter as usize);
-                    }
-                    if ui.small_button("Index Register").clicked() {
-                        self.memory_scroll_to = Some(metadata.index_register as usize);
-                    }
-                    if ui.small_button("Program Start").clicked() {
-                        self.memory_scroll_to = Some(0x200); // CHIP-8 program start
-                    }
-                    if ui.small_button("Font Start").clicked() {
-                        self.memory_scroll_to = Some(0x50); // CHIP-8 font start
-                    }
-                });
-
-                ui.separator();
-
-                let mut scroll_area = egui::ScrollArea::vertical()
-                    .id_salt("memory_scroll")
-                    .auto_shrink([false, false]);
-
-                if let Some(scroll_to) = self.memory_scroll_to.take() {
-                    let row = scroll_to / 16;
-                    scroll_area = scroll_area.vertical_scroll_offset((row as f32) * 18.0);
-                }
-
-                scroll_area.show(ui, |ui| {
-                    ui.style_mut().override_text_style = Some(egui::TextStyle::Monospace);
-
-                    for (addr, chunk) in metadata.memory.chunks(16).enumerate() {
-                        let base_addr = addr * 16;
-
-                        ui.horizontal(|ui| {
-                            // Address
-                            let addr_color = if base_addr == metadata.program_counter as usize {
-                                egui::Color32::YELLOW
-                            } else if base_addr <= metadata.index_register as usize
-                                && (metadata.index_register as usize) < base_addr + 16
-                            {
-                                egui::Color32::LIGHT_BLUE
-                            } else {
-                                egui::Color32::GRAY
-                            };
-
-                            ui.colored_label(addr_color, format!("{:04X}:", base_addr));
-
-                            // Hex bytes
-                            for (i, &byte) in chunk.iter().enumerate() {
-                                let byte_addr = base_addr + i;
-                                let color = if byte_addr == metadata.program_counter as usize {
-                                    egui::Color32::YELLOW
-                                } else if byte_addr == metadata.index_register as usize {
-                                    egui::Color32::LIGHT_BLUE
-                                } else if byte != 0 {
-                                    egui::Color32::WHITE
-                                } else {
-                                    egui::Color32::DARK_GRAY
-                                };
-
-                                ui.colored_label(color, format!("{:02X}", byte));
-                            }
-                        });
-                    }
-                });
+                self.memory_panel(ui);
             });
+        self.settings.layout.right_panel = Some(right_panel.response.rect.width());
 
         // --- BOTTOM PANEL: Instructions/Disassembly ---
         egui::TopBottomPanel::bottom("bottom_panel")
@@ -445,305 +2444,116 @@ impl eframe::App for App {
                 });
 
                 let metadata = self.emulator.metadata();
+                let pc = metadata.program_counter;
+                let fault_pc = self.fault.as_ref().map(|fault| fault.pc);
+
+                // PC landed somewhere the static analysis could not reach
+                // (e.g. a JP V0 target), so trace from there as well
+                if let Some(map) = &mut self.code_map {
+                    if map.range().contains(&pc) && map.kind(pc) != ByteKind::Code {
+                        map.trace(&metadata.memory, pc);
+                    }
+                }
+
+                let lines = match &self.code_map {
+                    Some(map) if map.range().contains(&pc) => {
+                        let lines = map.lines();
+                        let current = lines.partition_point(|line| line.addr <= pc);
+                        let start = current.saturating_sub(11);
+                        let end = (current + 20).min(lines.len());
+                        lines[start..end].to_vec()
+                    }
+                    _ => {
+                        // Show more instructions around PC for better context
+                        let start = (pc as usize).saturating_sub(20) & !1; // Align to even address
+                        let end = (pc as usize + 40).min(metadata.memory.len() - 1) & !1;
+                        (start..end)
+                            .step_by(2)
+                            .map(|addr| Line {
+                                addr: addr as u16,
+                                kind: ByteKind::Code,
+                            })
+                            .collect()
+                    }
+                };
 
                 egui::ScrollArea::vertical()
                     .auto_shrink([false, false])
                     .show(ui, |ui| {
                         ui.style_mut().override_text_style = Some(egui::TextStyle::Monospace);
 
-                        // Show more instructions around PC for better context
-                        let pc = metadata.program_counter as usize;
-                        let start = pc.saturating_sub(20) & !1; // Align to even address
-                        let end = (pc + 40).min(metadata.memory.len() - 1) & !1;
-
-                        for addr in (start..end).step_by(2) {
-                            if addr + 1 < metadata.memory.len() {
-                                let opcode = ((metadata.memory[addr] as u16) << 8)
-                                    | (metadata.memory[addr + 1] as u16);
-
-                                ui.horizontal(|ui| {
-                                    let is_current = addr == pc;
-                                    let color = if is_current {
-                                        egui::Color32::YELLOW
-                                    } else {
-                                        egui::Color32::WHITE
-                                    };
+                        for line in lines {
+                            let addr = line.addr as usize;
 
-                                    // Add yellowish background highlight for current instxn
-                                    if is_current {
-                                        ui.painter().rect_filled(
-                                            ui.available_rect_before_wrap(),
-                                            0.0,
-                                            egui::Color32::from_rgba_unmultiplied(255, 255, 0, 30),
-                                        );
-                                    }
+                            if let Some(label) =
+                                self.code_map.as_ref().and_then(|map| map.label(line.addr))
+                            {
+                                ui.colored_label(egui::Color32::LIGHT_GREEN, format!("{}:", label));
+                            }
+
+                            ui.horizontal(|ui| {
+                                let is_current = line.addr == pc;
+                                let is_fault = fault_pc == Some(line.addr);
+                                let color = if is_fault {
+                                    egui::Color32::RED
+                                } else if is_current {
+                                    egui::Color32::YELLOW
+                                } else if line.kind == ByteKind::Code {
+                                    egui::Color32::WHITE
+                                } else {
+                                    egui::Color32::GRAY
+                                };
+
+                                // Add yellowish background highlight for current instxn
+                                if is_fault {
+                                    ui.painter().rect_filled(
+                                        ui.available_rect_before_wrap(),
+                                        0.0,
+                                        egui::Color32::from_rgba_unmultiplied(255, 0, 0, 40),
+                                    );
+                                } else if is_current {
+                                    ui.painter().rect_filled(
+                                        ui.available_rect_before_wrap(),
+                                        0.0,
+                                        egui::Color32::from_rgba_unmultiplied(255, 255, 0, 30),
+                                    );
+                                }
+
+                                if line.kind == ByteKind::Code {
+                                    let opcode = ((metadata.memory[addr] as u16) << 8)
+                                        | (metadata.memory[addr + 1] as u16);
+                                    let (mnemonic, comment) = match &self.code_map {
+                                        Some(map) => disasm::decode_symbolic(opcode, map),
+                                        None => disasm::decode(opcode),
+                                    };
 
                                     ui.colored_label(
                                         color,
                                         format!("{:04X}: {:04X}", addr, opcode),
                                     );
-
                                     ui.separator();
+                                    ui.colored_label(
+                                        color,
+                                        format!("{:<20}; {}", mnemonic, comment),
+                                    );
+                                } else {
+                                    let byte = metadata.memory[addr];
 
-                                    let instruction = match opcode & 0xF000 {
-                                        0x0000 => match opcode {
-                                            0x00E0 => format!("{:<20}; Clear display", "CLS"),
-                                            0x00EE => {
-                                                format!("{:<20}; Return from subroutine", "RET")
-                                            }
-                                            _ => format!(
-                                                "{:<20}; Call system routine SYS {:03X}",
-                                                format!("SYS {:03X}", opcode & 0x0FFF),
-                                                opcode & 0x0FFF
-                                            ),
-                                        },
-                                        0x1000 => format!(
-                                            "{:<20}; Jump to address {:03X}",
-                                            format!("JP {:03X}", opcode & 0x0FFF),
-                                            opcode & 0x0FFF
-                                        ),
-                                        0x2000 => format!(
-                                            "{:<20}; Call subroutine at {:03X}",
-                                            format!("CALL {:03X}", opcode & 0x0FFF),
-                                            opcode & 0x0FFF
-                                        ),
-                                        0x3000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            let kk = opcode & 0x00FF;
-                                            format!(
-                                                "{:<20}; Skip if V{:X} == {:02X}",
-                                                format!("SE V{:X}, {:02X}", x, kk),
-                                                x,
-                                                kk
-                                            )
-                                        }
-                                        0x4000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            let kk = opcode & 0x00FF;
-                                            format!(
-                                                "{:<20}; Skip if V{:X} != {:02X}",
-                                                format!("SNE V{:X}, {:02X}", x, kk),
-                                                x,
-                                                kk
-                                            )
-                                        }
-                                        0x5000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            let y = (opcode & 0x00F0) >> 4;
-                                            format!(
-                                                "{:<20}; Skip if V{:X} == V{:X}",
-                                                format!("SE V{:X}, V{:X}", x, y),
-                                                x,
-                                                y
-                                            )
-                                        }
-                                        0x6000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            let kk = opcode & 0x00FF;
-                                            format!(
-                                                "{:<20}; Load {:02X} into V{:X}",
-                                                format!("LD V{:X}, {:02X}", x, kk),
-                                                kk,
-                                                x
-                                            )
-                                        }
-                                        0x7000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            let kk = opcode & 0x00FF;
-                                            format!(
-                                                "{:<20}; Add {:02X} to V{:X}",
-                                                format!("ADD V{:X}, {:02X}", x, kk),
-                                                kk,
-                                                x
-                                            )
-                                        }
-                                        0x8000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            let y = (opcode & 0x00F0) >> 4;
-                                            match opcode & 0x000F {
-                                                0x0 => format!(
-                                                    "{:<20}; V{:X} = V{:X}",
-                                                    format!("LD V{:X}, V{:X}", x, y),
-                                                    x,
-                                                    y
-                                                ),
-                                                0x1 => format!(
-                                                    "{:<20}; V{:X} |= V{:X}",
-                                                    format!("OR V{:X}, V{:X}", x, y),
-                                                    x,
-                                                    y
-                                                ),
-                                                0x2 => format!(
-                                                    "{:<20}; V{:X} &= V{:X}",
-                                                    format!("AND V{:X}, V{:X}", x, y),
-                                                    x,
-                                                    y
-                                                ),
-                                                0x3 => format!(
-                                                    "{:<20}; V{:X} ^= V{:X}",
-                                                    format!("XOR V{:X}, V{:X}", x, y),
-                                                    x,
-                                                    y
-                                                ),
-                                                0x4 => format!(
-                                                    "{:<20}; V{:X} += V{:X}, VF = carry",
-                                                    format!("ADD V{:X}, V{:X}", x, y),
-                                                    x,
-                                                    y
-                                                ),
-                                                0x5 => format!(
-                                                    "{:<20}; V{:X} -= V{:X}, VF = borrow",
-                                                    format!("SUB V{:X}, V{:X}", x, y),
-                                                    x,
-                                                    y
-                                                ),
-                                                0x6 => format!(
-                                                    "{:<20}; V{:X} >>= 1, VF = carry",
-                                                    format!("SHR V{:X}", x),
-                                                    x
-                                                ),
-                                                0x7 => format!(
-                                                    "{:<20}; V{:X} = V{:X} - V{:X}, VF = borrow",
-                                                    format!("SUBN V{:X}, V{:X}", x, y),
-                                                    x,
-                                                    y,
-                                                    x
-                                                ),
-                                                0xE => format!(
-                                                    "{:<20}; V{:X} <<= 1, VF = carry",
-                                                    format!("SHL V{:X}", x),
-                                                    x
-                                                ),
-                                                _ => format!(
-                                                    "{:<20}; Unknown 8xxx instruction",
-                                                    format!("8{:X}{:X}{:X}", x, y, opcode & 0x000F)
-                                                ),
-                                            }
-                                        }
-                                        0x9000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            let y = (opcode & 0x00F0) >> 4;
-                                            format!(
-                                                "{:<20}; Skip if V{:X} != V{:X}",
-                                                format!("SNE V{:X}, V{:X}", x, y),
-                                                x,
-                                                y
-                                            )
-                                        }
-                                        0xA000 => format!(
-                                            "{:<20}; I = {:03X}",
-                                            format!("LD I, {:03X}", opcode & 0x0FFF),
-                                            opcode & 0x0FFF
-                                        ),
-                                        0xB000 => format!(
-                                            "{:<20}; Jump to V0 + {:03X}",
-                                            format!("JP V0, {:03X}", opcode & 0x0FFF),
-                                            opcode & 0x0FFF
-                                        ),
-                                        0xC000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            let kk = opcode & 0x00FF;
-                                            format!(
-                                                "{:<20}; V{:X} = random() & {:02X}",
-                                                format!("RND V{:X}, {:02X}", x, kk),
-                                                x,
-                                                kk
-                                            )
-                                        }
-                                        0xD000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            let y = (opcode & 0x00F0) >> 4;
-                                            let n = opcode & 0x000F;
-                                            format!(
-                                                "{:<20}; Draw sprite at (V{:X}, V{:X}) height {:X}",
-                                                format!("DRW V{:X}, V{:X}, {:X}", x, y, n),
-                                                x,
-                                                y,
-                                                n
-                                            )
-                                        }
-                                        0xE000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            match opcode & 0x00FF {
-                                                0x9E => format!(
-                                                    "{:<20}; Skip if key V{:X} pressed",
-                                                    format!("SKP V{:X}", x),
-                                                    x
-                                                ),
-                                                0xA1 => format!(
-                                                    "{:<20}; Skip if key V{:X} not pressed",
-                                                    format!("SKNP V{:X}", x),
-                                                    x
-                                                ),
-                                                _ => format!(
-                                                    "{:<20}; Unknown Exxx instruction",
-                                                    format!("E{:X}{:02X}", x, opcode & 0x00FF)
-                                                ),
-                                            }
-                                        }
-                                        0xF000 => {
-                                            let x = (opcode & 0x0F00) >> 8;
-                                            match opcode & 0x00FF {
-                                                0x07 => format!(
-                                                    "{:<20}; V{:X} = delay timer",
-                                                    format!("LD V{:X}, DT", x),
-                                                    x
-                                                ),
-                                                0x0A => format!(
-                                                    "{:<20}; Wait for key, store in V{:X}",
-                                                    format!("LD V{:X}, K", x),
-                                                    x
-                                                ),
-                                                0x15 => format!(
-                                                    "{:<20}; Delay timer = V{:X}",
-                                                    format!("LD DT, V{:X}", x),
-                                                    x
-                                                ),
-                                                0x18 => format!(
-                                                    "{:<20}; Sound timer = V{:X}",
-                                                    format!("LD ST, V{:X}", x),
-                                                    x
-                                                ),
-                                                0x1E => format!(
-                                                    "{:<20}; I += V{:X}",
-                                                    format!("ADD I, V{:X}", x),
-                                                    x
-                                                ),
-                                                0x29 => format!(
-                                                    "{:<20}; I = sprite address for digit V{:X}",
-                                                    format!("LD F, V{:X}", x),
-                                                    x
-                                                ),
-                                                0x33 => format!(
-                                                    "{:<20}; Store BCD of V{:X} at [I]",
-                                                    format!("LD B, V{:X}", x),
-                                                    x
-                                                ),
-                                                0x55 => format!(
-                                                    "{:<20}; Store V0-V{:X} at [I]",
-                                                    format!("LD [I], V{:X}", x),
-                                                    x
-                                                ),
-                                                0x65 => format!(
-                                                    "{:<20}; Load V0-V{:X} from [I]",
-                                                    format!("LD V{:X}, [I]", x),
-                                                    x
-                                                ),
-                                                _ => format!(
-                                                    "{:<20}; Unknown Fxxx instruction",
-                                                    format!("F{:X}{:02X}", x, opcode & 0x00FF)
-                                                ),
-                                            }
-                                        }
-                                        _ => format!(
-                                            "{:<20}; Unrecognized instruction",
-                                            format!("UNKNOWN {:04X}", opcode)
+                                    ui.colored_label(
+                                        color,
+                                        format!("{:04X}: {:02X}  ", addr, byte),
+                                    );
+                                    ui.separator();
+                                    ui.colored_label(
+                                        color,
+                                        format!(
+                                            "{:<20}; {}",
+                                            format!("DB 0x{:02X}", byte),
+                                            disasm::sprite_row(byte)
                                         ),
-                                    };
-
-                                    ui.colored_label(color, instruction);
-                                });
-                            }
+                                    );
+                                }
+                            });
                         }
                     });
             });
@@ -753,38 +2563,185 @@ impl eframe::App for App {
             if let Some(tex) = &self.texture {
                 let available_rect = ui.available_rect_before_wrap();
 
-                // Make display smaller to give more space to panels
-                let max_scale = 12.0; // Reduced from previous scaling
-                let tex_size = tex.size_vec2();
+                // The texture may be upscaled by post-processing, lay out by
+                // the emulated resolution
+                let (width, height) = self.emulator.resolution();
+                let tex_size = egui::vec2(width as f32, height as f32);
                 let scale_x = available_rect.width() / tex_size.x;
                 let scale_y = available_rect.height() / tex_size.y;
-                let scale = (scale_x.min(scale_y)).min(max_scale).max(2.0);
+                let scale = self.post_process.display_scale(scale_x.min(scale_y));
 
                 let display_size = tex_size * scale;
 
                 // Center the display
                 let center_pos = available_rect.center() - display_size / 2.0;
 
-                let mut child_ui = ui.new_child(egui::UiBuilder::new().max_rect(egui::Rect::from_min_size(center_pos, display_size)));
+                let display_rect = egui::Rect::from_min_size(center_pos, display_size);
+                let mut child_ui = ui.new_child(egui::UiBuilder::new().max_rect(display_rect));
                 child_ui.image((tex.id(), display_size));
+
+                if self.show_draw_overlay {
+                    let metadata = self.emulator.metadata();
+                    if let Some(draw) = &metadata.last_draw {
+                        Self::draw_overlay(ui.painter(), display_rect, scale, draw);
+                    }
+                }
             } else {
                 ui.centered_and_justified(|ui| {
                     ui.label("Loading display...");
                 });
             }
         });
+
+        // --- Persist settings ---
+        // Window geometry and panel sizes are only saved on exit
+        let (closing, inner, outer) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (
+                viewport.close_requested(),
+                viewport.inner_rect,
+                viewport.outer_rect,
+            )
+        });
+        if closing {
+            if self.recorder.is_some() {
+                self.toggle_recording();
+            }
+            // Keep the input recorded so far
+            self.stop_movie();
+            self.settings.layout.window_size = inner.map(|rect| rect.size().into());
+            self.settings.layout.window_position = outer.map(|rect| rect.min.into());
+            self.save_settings();
+        } else if self.settings_dirty
+            && !ctx.input(|i| i.pointer.any_down())
+            && !ctx.wants_keyboard_input()
+        {
+            // Wait for slider drags and typing to finish
+            self.save_settings();
+        }
     }
 }
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init();
     let cli = Cli::parse();
+    if let Some(cycles) = cli.cycles {
+        log::warn!(
+            "--cycles is deprecated, use --frequency {}",
+            cycles.saturating_mul(60)
+        );
+    }
+
+    match &cli.command {
+        Some(Command::Disasm { rom }) => {
+            match std::fs::read(rom) {
+                Ok(data) => print!("{}", disasm::rom_listing(&data)),
+                Err(e) => {
+                    log::error!("Failed to read ROM {:?}: {}", rom, e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Asm { source, output }) => {
+            let output = output
+                .clone()
+                .unwrap_or_else(|| source.with_extension("ch8"));
+            let result = std::fs::read_to_string(source)
+                .map_err(|e| e.to_string())
+                .and_then(|text| asm::assemble(&text).map_err(|e| e.to_string()))
+                .and_then(|program| std::fs::write(&output, program).map_err(|e| e.to_string()));
+            if let Err(e) = result {
+                log::error!("Failed to assemble {:?}: {}", source, e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Test { manifest, output }) => {
+            match regression::run(manifest, output) {
+                Ok(summary) if summary.failed == 0 => {}
+                Ok(_) => std::process::exit(1),
+                Err(e) => {
+                    log::error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    if let (Some(out), Some(frames)) = (&cli.record, cli.frames) {
+        let result = headless(&cli)
+            .and_then(|mut headless| headless.record(out, frames).map_err(|e| e.to_string()));
+        match result {
+            Ok(frames) => log::info!("Recorded {} frames to {:?}", frames, out),
+            Err(e) => {
+                log::error!("Recording failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(args) = &cli.screenshot_at_frame {
+        let result = args[0]
+            .parse::<u64>()
+            .map_err(|e| format!("invalid frame '{}': {}", args[0], e))
+            .and_then(|frames| {
+                let mut headless = headless(&cli)?;
+                headless.run_frames(frames).map_err(|e| e.to_string())?;
+                headless
+                    .screenshot(Path::new(&args[1]))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::error!("Screenshot failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(movie) = &cli.play_movie {
+        let result = headless(&cli).and_then(|mut headless| {
+            let ticks = headless.movie_ticks().unwrap_or(0);
+            headless.run_frames(ticks).map_err(|e| e.to_string())?;
+            Ok(headless)
+        });
+        match result {
+            Ok(headless) => {
+                let metadata = headless.emulator.metadata();
+                println!(
+                    "Played {} ticks from {:?}, PC={:04X} I={:04X} V={:02X?}",
+                    headless.frame,
+                    movie,
+                    metadata.program_counter,
+                    metadata.index_register,
+                    metadata.registers
+                );
+            }
+            Err(e) => {
+                log::error!("Playback failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
+    let (settings, settings_warning) = Settings::load_or_default();
+    if let Some(warning) = &settings_warning {
+        log::warn!("{}", warning);
+    }
+    let layout = settings.layout;
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size(layout.window_size.unwrap_or([1400.0, 900.0])) // Larger default for more info panels
+        .with_min_inner_size([1000.0, 700.0])
+        .with_resizable(true);
+    if let Some(position) = layout.window_position {
+        viewport = viewport.with_position(position);
+    }
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1400.0, 900.0]) // Larger default for more info panels
-            .with_min_inner_size([1000.0, 700.0])
-            .with_resizable(true),
+        viewport,
         ..Default::default()
     };
 
@@ -792,6 +2749,14 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Multi-Emulator",
         options,
-        Box::new(|_cc| Ok(Box::new(App::new(cli.cycles, cli.mute)))),
+        Box::new(|_cc| {
+            let mut app = App::new(&cli, settings, settings_warning);
+            if let Some(rom) = &cli.rom {
+                app.load_rom(rom);
+                // Command line options win over what was saved for the ROM
+                app.apply_profile(cli.profile(app.profile()));
+            }
+            Ok(Box::new(app))
+        }),
     )
 }
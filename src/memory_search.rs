@@ -0,0 +1,158 @@
+/// Parse a hex byte pattern such as `A2 1E`, `a21e` or `A2 ?? 1E`, where `??`
+/// matches any byte
+pub fn parse_pattern(text: &str) -> Option<Vec<Option<u8>>> {
+    let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            b"??" => Some(None),
+            // from_str_radix would also take a sign
+            _ if pair.iter().all(u8::is_ascii_hexdigit) => std::str::from_utf8(pair)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .map(Some),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Start addresses of every occurrence of `pattern` in `memory`
+pub fn find_all(memory: &[u8], pattern: &[Option<u8>]) -> Vec<usize> {
+    if pattern.is_empty() || pattern.len() > memory.len() {
+        return Vec::new();
+    }
+
+    memory
+        .windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| {
+            window
+                .iter()
+                .zip(pattern)
+                .all(|(byte, expected)| expected.is_none_or(|expected| *byte == expected))
+        })
+        .map(|(addr, _)| addr)
+        .collect()
+}
+
+/// How a candidate's value must relate to the previous snapshot to be kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueFilter {
+    Equal(u8),
+    Increased,
+    Decreased,
+    Unchanged,
+    Changed,
+}
+
+/// Narrow down the address of a value (lives, score, ...) by repeatedly
+/// comparing memory against the previous snapshot
+#[derive(Debug, Clone)]
+pub struct ValueSearch {
+    candidates: Vec<usize>,
+    snapshot: Vec<u8>,
+}
+
+impl ValueSearch {
+    /// Start a search where every address is a candidate
+    pub fn new(memory: &[u8]) -> Self {
+        Self {
+            candidates: (0..memory.len()).collect(),
+            snapshot: memory.to_vec(),
+        }
+    }
+
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+
+    /// Keep only the candidates matching `filter` and take a new snapshot
+    pub fn filter(&mut self, memory: &[u8], filter: ValueFilter) {
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|&addr| {
+            let (Some(&old), Some(&new)) = (snapshot.get(addr), memory.get(addr)) else {
+                return false;
+            };
+            match filter {
+                ValueFilter::Equal(value) => new == value,
+                ValueFilter::Increased => new > old,
+                ValueFilter::Decreased => new < old,
+                ValueFilter::Unchanged => new == old,
+                ValueFilter::Changed => new != old,
+            }
+        });
+        self.snapshot = memory.to_vec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_parse_with_wildcards() {
+        assert_eq!(
+            parse_pattern("A2 ?? 1e"),
+            Some(vec![Some(0xA2), None, Some(0x1E)])
+        );
+        assert_eq!(parse_pattern("a21E"), parse_pattern("A2 1E"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        for text in ["", "   ", "A", "A2 1", "A2 1E F", "G1", "+1", "A?", "0x12"] {
+            assert_eq!(parse_pattern(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn overlapping_matches_are_all_found() {
+        let memory = [0xAA, 0xAA, 0xAA, 0x12, 0xAA, 0x34];
+        assert_eq!(find_all(&memory, &[Some(0xAA), Some(0xAA)]), [0, 1]);
+        assert_eq!(find_all(&memory, &[Some(0xAA), None]), [0, 1, 2, 4]);
+        assert_eq!(find_all(&memory, &[None; 7]), Vec::<usize>::new());
+        assert_eq!(find_all(&memory, &[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn value_searches_narrow_by_change() {
+        let mut memory = [3, 7, 7, 0];
+        let mut search = ValueSearch::new(&memory);
+        assert_eq!(search.candidates(), [0, 1, 2, 3]);
+
+        // A life lost at address 0, a counter ticking at address 3
+        memory[0] = 2;
+        memory[3] = 1;
+        search.filter(&memory, ValueFilter::Changed);
+        assert_eq!(search.candidates(), [0, 3]);
+
+        memory[3] = 2;
+        search.filter(&memory, ValueFilter::Unchanged);
+        assert_eq!(search.candidates(), [0]);
+
+        memory[0] = 1;
+        let mut decreased = search.clone();
+        decreased.filter(&memory, ValueFilter::Decreased);
+        assert_eq!(decreased.candidates(), [0]);
+        search.filter(&memory, ValueFilter::Increased);
+        assert!(search.candidates().is_empty());
+    }
+
+    #[test]
+    fn value_searches_compare_against_the_latest_snapshot() {
+        let mut search = ValueSearch::new(&[5, 5]);
+        search.filter(&[5, 6], ValueFilter::Equal(5));
+        assert_eq!(search.candidates(), [0]);
+        // Equal to the previous snapshot now, so unchanged
+        search.filter(&[5, 6], ValueFilter::Unchanged);
+        assert_eq!(search.candidates(), [0]);
+        // Addresses past the end of a smaller memory are dropped
+        search.filter(&[], ValueFilter::Unchanged);
+        assert!(search.candidates().is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+use crate::emulators::chip8::configs::{DEFAULT_INSTRUCTION_HZ, DEFAULT_SEED};
+use crate::emulators::ExecutionPolicy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// File extension of input movies
+pub const EXTENSION: &str = "c8m";
+
+#[derive(Debug, Error)]
+pub enum MovieError {
+    #[error("I/O error on movie '{path:?}': {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid movie '{path:?}': {source}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("Failed to serialize movie: {0}")]
+    Serialize(#[from] toml::ser::Error),
+
+    #[error("Movie was recorded on {expected}, not {actual}")]
+    SystemMismatch { expected: String, actual: String },
+
+    #[error("Movie was recorded with ROM {expected}, the loaded ROM is {actual}")]
+    RomMismatch { expected: String, actual: String },
+}
+
+/// Everything besides the inputs that affects how a session plays out
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MovieSettings {
+    /// Instructions per second, unused with VIP timing
+    pub frequency: u32,
+    pub vip_timing: bool,
+    pub display_wait: bool,
+    pub policy: ExecutionPolicy,
+    /// Initial state of the random number generator
+    pub seed: u8,
+}
+
+impl Default for MovieSettings {
+    fn default() -> Self {
+        Self {
+            frequency: DEFAULT_INSTRUCTION_HZ,
+            vip_timing: false,
+            display_wait: false,
+            policy: ExecutionPolicy::default(),
+            seed: DEFAULT_SEED,
+        }
+    }
+}
+
+/// Keypad state for every 60 Hz tick of a session, from reset onwards.
+/// Playing it back against the same ROM and settings reproduces the session
+/// exactly, as emulation is deterministic.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Movie {
+    pub system: String,
+    /// SHA-1 of the program as loaded, after assembling source files
+    pub rom_sha1: String,
+    /// One bit per key (bit N is key N) for every tick
+    #[serde(with = "runs")]
+    pub inputs: Vec<u16>,
+    pub settings: MovieSettings,
+}
+
+impl Movie {
+    pub fn new(system: &str, program: &[u8], settings: MovieSettings) -> Self {
+        Self {
+            system: system.to_string(),
+            rom_sha1: sha1(program),
+            inputs: Vec::new(),
+            settings,
+        }
+    }
+
+    /// Number of ticks recorded
+    pub fn ticks(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Append the keys held during the next tick
+    pub fn push(&mut self, keys: &[bool]) {
+        let mask = keys
+            .iter()
+            .take(16)
+            .enumerate()
+            .fold(0, |mask, (key, &down)| mask | u16::from(down) << key);
+        self.inputs.push(mask);
+    }
+
+    /// Keys held during `tick`, or `None` past the end of the movie
+    pub fn keys(&self, tick: usize) -> Option<[bool; 16]> {
+        let mask = *self.inputs.get(tick)?;
+        Some(std::array::from_fn(|key| mask & 1 << key != 0))
+    }
+
+    /// Check that the movie was made for this system and program
+    pub fn verify(&self, system: &str, program: &[u8]) -> Result<(), MovieError> {
+        if self.system != system {
+            return Err(MovieError::SystemMismatch {
+                expected: self.system.clone(),
+                actual: system.to_string(),
+            });
+        }
+        let actual = sha1(program);
+        if self.rom_sha1 != actual {
+            return Err(MovieError::RomMismatch {
+                expected: self.rom_sha1.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, MovieError> {
+        let text = std::fs::read_to_string(path).map_err(|source| MovieError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|source| MovieError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MovieError> {
+        let text = toml::to_string(self)?;
+        std::fs::write(path, text).map_err(|source| MovieError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+/// Hex SHA-1 digest, as used for ROM and framebuffer hashes
+pub fn sha1(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
+
+/// Inputs stored run-length encoded as `[ticks, keys]` pairs, as keys are
+/// usually held for many ticks
+mod runs {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Longest movie accepted, a day of ticks, so a corrupt run length
+    /// cannot exhaust memory
+    pub const MAX_TICKS: u64 = 60 * 60 * 60 * 24;
+
+    pub fn serialize<S: Serializer>(inputs: &[u16], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut runs: Vec<(u32, u16)> = Vec::new();
+        for &mask in inputs {
+            match runs.last_mut() {
+                Some((ticks, last)) if *last == mask => *ticks += 1,
+                _ => runs.push((1, mask)),
+            }
+        }
+        serializer.collect_seq(runs)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u16>, D::Error> {
+        let runs = Vec::<(u32, u16)>::deserialize(deserializer)?;
+        let total: u64 = runs.iter().map(|&(ticks, _)| u64::from(ticks)).sum();
+        if total > MAX_TICKS {
+            return Err(D::Error::custom(format!(
+                "{} ticks of input, at most {} are supported",
+                total, MAX_TICKS
+            )));
+        }
+        Ok(runs
+            .into_iter()
+            .flat_map(|(ticks, mask)| std::iter::repeat_n(mask, ticks as usize))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulators::chip8::{asm, Chip8Emulator};
+    use crate::emulators::Emulator;
+    use crate::headless::Headless;
+    use crate::regression::framebuffer_hash;
+    use crate::scheduler::Clock;
+
+    /// Moves a digit right while key 5 is held
+    const PROGRAM: &str = "
+            LD V0, 5
+        loop:
+            SKNP V0
+            ADD V1, 1
+            CLS
+            LD F, V1
+            DRW V1, V2, 5
+            JP loop
+    ";
+
+    fn session(program: &[u8]) -> Headless {
+        let mut emulator = Chip8Emulator::new();
+        emulator.load_program(program).unwrap();
+        Headless::new(emulator, Clock::Instructions { hz: 600 })
+    }
+
+    fn screen(headless: &mut Headless) -> String {
+        headless.emulator.frame_changed();
+        framebuffer_hash(headless.emulator.framebuffer())
+    }
+
+    #[test]
+    fn replaying_a_recording_reproduces_the_session() {
+        let program = asm::assemble(PROGRAM).unwrap();
+        let mut recording = session(&program);
+        let mut movie = Movie::new("CHIP-8", &program, MovieSettings::default());
+        for frame in 0..120 {
+            let mut keys = [false; 16];
+            keys[5] = frame % 7 < 3;
+            recording.emulator.set_input_state(&keys);
+            movie.push(&keys);
+            recording.run_frame().unwrap();
+        }
+        let recorded = screen(&mut recording);
+
+        let movie: Movie = toml::from_str(&toml::to_string(&movie).unwrap()).unwrap();
+        assert_eq!(movie.ticks(), 120);
+        let mut replay = session(&program);
+        replay.play(movie);
+        replay.run_frames(120).unwrap();
+        assert_eq!(screen(&mut replay), recorded);
+
+        // The inputs made a difference
+        let mut idle = session(&program);
+        idle.run_frames(120).unwrap();
+        assert_ne!(screen(&mut idle), recorded);
+    }
+
+    #[test]
+    fn oversized_runs_are_rejected() {
+        let text = r#"
+            system = "CHIP-8"
+            rom_sha1 = ""
+            inputs = [[4294967295, 1]]
+            [settings]
+        "#;
+        let error = toml::from_str::<Movie>(text).unwrap_err();
+        assert!(error.to_string().contains("at most"), "{}", error);
+
+        let text = text.replace("4294967295", "3");
+        let movie: Movie = toml::from_str(&text).unwrap();
+        assert_eq!(movie.inputs, [1, 1, 1]);
+    }
+}
@@ -0,0 +1,502 @@
+use crate::emulators::{EmuError, Emulator};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Recordings advance one frame per 60 Hz tick
+const FPS: u64 = 60;
+const SAMPLE_RATE: u32 = 44_100;
+const BEEP_HZ: u32 = 440;
+
+#[derive(Debug, Error)]
+pub enum RecordError {
+    #[error("Unsupported recording format '{path:?}', use .gif or .y4m")]
+    UnsupportedFormat { path: PathBuf },
+
+    #[error("Display of {width}x{height} is too large for a GIF")]
+    TooLarge { width: usize, height: usize },
+
+    #[error("I/O error while recording '{path:?}': {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Failed to encode GIF '{path:?}': {source}")]
+    Gif {
+        path: PathBuf,
+        #[source]
+        source: gif::EncodingError,
+    },
+
+    #[error("Emulation stopped after {frames} recorded frames: {source}")]
+    Emulation {
+        frames: u64,
+        #[source]
+        source: EmuError,
+    },
+}
+
+/// Container for a recording, picked by file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Animated GIF, video only
+    Gif,
+    /// Uncompressed YUV4MPEG2 video plus a WAV file with the same name
+    Y4m,
+}
+
+impl RecordFormat {
+    pub const ALL: [RecordFormat; 2] = [RecordFormat::Gif, RecordFormat::Y4m];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordFormat::Gif => "gif",
+            RecordFormat::Y4m => "y4m",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecordFormat::Gif => "GIF",
+            RecordFormat::Y4m => "Y4M + WAV",
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension().eq_ignore_ascii_case(extension))
+    }
+}
+
+enum Output {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        /// Last distinct frame and the tick it first appeared at. Identical
+        /// frames are merged into one longer GIF frame.
+        pending: Option<(Vec<u32>, u64)>,
+    },
+    Y4m {
+        video: BufWriter<File>,
+        audio: WavWriter,
+    },
+}
+
+/// Writes the display (and beeper, where the format allows) once per tick.
+/// Dropping a recorder finishes it, so the file stays playable.
+pub struct Recorder {
+    path: PathBuf,
+    output: Output,
+    width: usize,
+    height: usize,
+    scale: usize,
+    frames: u64,
+    finished: bool,
+}
+
+impl Recorder {
+    /// Start recording a `width` x `height` display, each pixel enlarged to
+    /// `scale` x `scale`
+    pub fn create(
+        path: &Path,
+        width: usize,
+        height: usize,
+        scale: usize,
+    ) -> Result<Self, RecordError> {
+        let scale = scale.max(1);
+        let format =
+            RecordFormat::from_path(path).ok_or_else(|| RecordError::UnsupportedFormat {
+                path: path.to_path_buf(),
+            })?;
+        let (out_width, out_height) = (width * scale, height * scale);
+        let io_error = |source| RecordError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let file = File::create(path).map_err(io_error)?;
+
+        let output = match format {
+            RecordFormat::Gif => {
+                let (Ok(gif_width), Ok(gif_height)) =
+                    (u16::try_from(out_width), u16::try_from(out_height))
+                else {
+                    return Err(RecordError::TooLarge { width, height });
+                };
+                let gif_error = |source| RecordError::Gif {
+                    path: path.to_path_buf(),
+                    source,
+                };
+                let mut encoder =
+                    gif::Encoder::new(BufWriter::new(file), gif_width, gif_height, &[])
+                        .map_err(gif_error)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(gif_error)?;
+                Output::Gif {
+                    encoder,
+                    pending: None,
+                }
+            }
+            RecordFormat::Y4m => {
+                let mut video = BufWriter::new(file);
+                writeln!(
+                    video,
+                    "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                    out_width, out_height, FPS
+                )
+                .map_err(io_error)?;
+                let wav_path = path.with_extension("wav");
+                let audio = WavWriter::create(&wav_path).map_err(|source| RecordError::Io {
+                    path: wav_path,
+                    source,
+                })?;
+                Output::Y4m { video, audio }
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            output,
+            width,
+            height,
+            scale,
+            frames: 0,
+            finished: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Record the emulator's current display and beeper state as one frame
+    pub fn capture<E: Emulator + ?Sized>(&mut self, emulator: &E) -> Result<(), RecordError> {
+        self.add_frame(emulator.framebuffer(), emulator.sound_active())
+    }
+
+    /// Record one tick worth of display (ARGB8888) and sound
+    pub fn add_frame(&mut self, pixels: &[u32], beeping: bool) -> Result<(), RecordError> {
+        let frame = self.frames;
+        self.frames += 1;
+
+        let pending_frame = match &mut self.output {
+            Output::Gif { pending, .. } => match pending {
+                Some((previous, _)) if previous.as_slice() == pixels => return Ok(()),
+                _ => pending.replace((pixels.to_vec(), frame)),
+            },
+            Output::Y4m { video, audio } => {
+                let io_error = |source| RecordError::Io {
+                    path: self.path.clone(),
+                    source,
+                };
+                write_y4m_frame(video, pixels, self.width, self.scale).map_err(io_error)?;
+                audio.write_tick(beeping).map_err(io_error)?;
+                return Ok(());
+            }
+        };
+
+        match pending_frame {
+            Some((pixels, start)) => self.write_gif_frame(&pixels, start, frame),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush everything and close the files. Returns the number of frames.
+    pub fn finish(mut self) -> Result<u64, RecordError> {
+        self.close()
+    }
+
+    fn close(&mut self) -> Result<u64, RecordError> {
+        let end = self.frames;
+        if std::mem::replace(&mut self.finished, true) {
+            return Ok(end);
+        }
+        match &mut self.output {
+            Output::Gif { pending, .. } => {
+                if let Some((pixels, start)) = pending.take() {
+                    self.write_gif_frame(&pixels, start, end)?;
+                }
+            }
+            Output::Y4m { video, audio } => {
+                let io_error = |source| RecordError::Io {
+                    path: self.path.clone(),
+                    source,
+                };
+                video.flush().map_err(io_error)?;
+                audio.finish().map_err(io_error)?;
+            }
+        }
+        Ok(end)
+    }
+
+    /// Write a frame shown from tick `start` until tick `end`
+    fn write_gif_frame(&mut self, pixels: &[u32], start: u64, end: u64) -> Result<(), RecordError> {
+        let Output::Gif { encoder, .. } = &mut self.output else {
+            return Ok(());
+        };
+
+        // GIF delays are in centiseconds, round tick boundaries so they add up
+        let centis = |tick: u64| tick * 100 / FPS;
+        let delay = (centis(end) - centis(start)).max(1);
+
+        let mut colors: Vec<u32> = Vec::new();
+        let mut indices = Vec::with_capacity(pixels.len() * self.scale * self.scale);
+        for row in pixels.chunks(self.width) {
+            let line: Vec<u8> = row
+                .iter()
+                .flat_map(|&argb| {
+                    let index = match colors.iter().position(|&c| c == argb) {
+                        Some(index) => index,
+                        None => {
+                            colors.push(argb);
+                            colors.len() - 1
+                        }
+                    };
+                    std::iter::repeat_n(index.min(255) as u8, self.scale)
+                })
+                .collect();
+            for _ in 0..self.scale {
+                indices.extend_from_slice(&line);
+            }
+        }
+        if colors.len() > 256 {
+            log::warn!("Frame has more than 256 colours, extra colours are merged");
+        }
+
+        // Palettes must have a power of two number of entries
+        let mut palette: Vec<u8> = colors
+            .iter()
+            .take(256)
+            .flat_map(|&argb| [(argb >> 16) as u8, (argb >> 8) as u8, argb as u8])
+            .collect();
+        palette.resize(colors.len().clamp(2, 256).next_power_of_two() * 3, 0);
+
+        let mut frame = gif::Frame::from_palette_pixels(
+            (self.width * self.scale) as u16,
+            (self.height * self.scale) as u16,
+            indices,
+            palette,
+            None,
+        );
+        frame.delay = delay as u16;
+        encoder
+            .write_frame(&frame)
+            .map_err(|source| RecordError::Gif {
+                path: self.path.clone(),
+                source,
+            })
+    }
+}
+
+/// One YUV 4:4:4 frame, BT.601 full range
+fn write_y4m_frame(
+    out: &mut impl Write,
+    pixels: &[u32],
+    width: usize,
+    scale: usize,
+) -> std::io::Result<()> {
+    let mut planes = [Vec::new(), Vec::new(), Vec::new()];
+    for row in pixels.chunks(width) {
+        let mut lines = [Vec::new(), Vec::new(), Vec::new()];
+        for &argb in row {
+            let [r, g, b] = [(argb >> 16) as u8, (argb >> 8) as u8, argb as u8].map(f32::from);
+            let y = 0.299 * r + 0.587 * g + 0.114 * b;
+            let u = 128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b;
+            let v = 128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+            for (line, value) in lines.iter_mut().zip([y, u, v]) {
+                line.extend(std::iter::repeat_n(
+                    value.round().clamp(0.0, 255.0) as u8,
+                    scale,
+                ));
+            }
+        }
+        for (plane, line) in planes.iter_mut().zip(&lines) {
+            for _ in 0..scale {
+                plane.extend_from_slice(line);
+            }
+        }
+    }
+
+    out.write_all(b"FRAME\n")?;
+    for plane in &planes {
+        out.write_all(plane)?;
+    }
+    Ok(())
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+/// 16-bit mono PCM WAV with the beeper as a square wave
+struct WavWriter {
+    out: BufWriter<File>,
+    samples: u32,
+    phase: f64,
+}
+
+impl WavWriter {
+    fn create(path: &Path) -> std::io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        // Sizes are filled in by `finish`
+        out.write_all(b"RIFF\0\0\0\0WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?; // PCM
+        out.write_all(&1u16.to_le_bytes())?; // Mono
+        out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        out.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?; // Byte rate
+        out.write_all(&2u16.to_le_bytes())?; // Block align
+        out.write_all(&16u16.to_le_bytes())?; // Bits per sample
+        out.write_all(b"data\0\0\0\0")?;
+        Ok(Self {
+            out,
+            samples: 0,
+            phase: 0.0,
+        })
+    }
+
+    fn write_tick(&mut self, beeping: bool) -> std::io::Result<()> {
+        for _ in 0..SAMPLE_RATE / FPS as u32 {
+            let sample: i16 = match (beeping, self.phase < 0.5) {
+                (false, _) => 0,
+                (true, true) => 8000,
+                (true, false) => -8000,
+            };
+            self.phase = (self.phase + BEEP_HZ as f64 / SAMPLE_RATE as f64).fract();
+            self.out.write_all(&sample.to_le_bytes())?;
+            self.samples += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        let data_size = self.samples * 2;
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(36 + data_size).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&data_size.to_le_bytes())?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ON: u32 = 0xFFFF_FFFF;
+    const OFF: u32 = 0xFF00_0000;
+
+    /// A fresh directory under the system temp directory
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chip8-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn gifs_merge_repeated_frames_and_keep_time() {
+        let dir = temp_dir("record-gif");
+        let path = dir.join("clip.gif");
+        let mut recorder = Recorder::create(&path, 2, 1, 3).unwrap();
+        for frame in [
+            [ON, OFF],
+            [ON, OFF],
+            [ON, OFF],
+            [OFF, ON],
+            [OFF, ON],
+            [ON, OFF],
+        ] {
+            recorder.add_frame(&frame, false).unwrap();
+        }
+        assert_eq!(recorder.finish().unwrap(), 6);
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(File::open(&path).unwrap()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (6, 3));
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        // 3, 2 and 1 ticks: 5, 3 and 2 centiseconds adding up to 1/10 s
+        assert_eq!(delays, [5, 3, 2]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn y4m_frames_follow_the_header() {
+        let dir = temp_dir("record-y4m");
+        let path = dir.join("clip.y4m");
+        let mut recorder = Recorder::create(&path, 2, 1, 2).unwrap();
+        for _ in 0..3 {
+            recorder.add_frame(&[ON, OFF], false).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let video = std::fs::read(&path).unwrap();
+        let header = b"YUV4MPEG2 W4 H2 F60:1 Ip A1:1 C444\n";
+        assert!(video.starts_with(header));
+        // "FRAME\n" and three 4x2 planes per frame
+        let frame_size = 6 + 4 * 2 * 3;
+        assert_eq!(video.len(), header.len() + 3 * frame_size);
+        let frame = &video[header.len()..][..frame_size];
+        assert_eq!(&frame[..6], b"FRAME\n");
+        assert_eq!(&frame[6..14], [255, 255, 0, 0, 255, 255, 0, 0]);
+        assert!(frame[14..].iter().all(|&chroma| chroma == 128));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wav_sizes_are_filled_in_on_finish() {
+        let dir = temp_dir("record-wav");
+        let mut recorder = Recorder::create(&dir.join("clip.y4m"), 2, 1, 1).unwrap();
+        for beeping in [false, true, true] {
+            recorder.add_frame(&[OFF, OFF], beeping).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let audio = std::fs::read(dir.join("clip.wav")).unwrap();
+        let size = |at: usize| u32::from_le_bytes(audio[at..at + 4].try_into().unwrap());
+        let data_size = 3 * (SAMPLE_RATE / FPS as u32) * 2;
+        assert_eq!(audio.len(), 44 + data_size as usize);
+        assert_eq!(&audio[..4], b"RIFF");
+        assert_eq!(size(4), 36 + data_size);
+        assert_eq!(&audio[36..40], b"data");
+        assert_eq!(size(40), data_size);
+        // Silent first tick, then the beep
+        let samples = &audio[44..];
+        let tick = (SAMPLE_RATE / FPS as u32) as usize * 2;
+        assert!(samples[..tick].iter().all(|&byte| byte == 0));
+        assert!(samples[tick..].iter().any(|&byte| byte != 0));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dropped_recorders_are_finished() {
+        let dir = temp_dir("record-drop");
+        let mut recorder = Recorder::create(&dir.join("clip.y4m"), 1, 1, 1).unwrap();
+        recorder.add_frame(&[OFF], false).unwrap();
+        drop(recorder);
+
+        let audio = std::fs::read(dir.join("clip.wav")).unwrap();
+        assert_ne!(&audio[40..44], [0; 4]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,334 @@
+use crate::emulators::chip8::Chip8Emulator;
+use crate::emulators::Emulator;
+use crate::headless::Headless;
+use crate::movie::{self, Movie, MovieSettings};
+use crate::screenshot;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RegressionError {
+    #[error("I/O error on manifest '{path:?}': {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid manifest '{path:?}': {source}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+}
+
+/// A list of ROMs to run headlessly and the display each must end up with.
+/// Paths are relative to the manifest.
+///
+/// ```toml
+/// [[test]]
+/// name = "keypad"
+/// rom = "keypad.8o"
+/// frames = 60
+/// press = [{ key = 5, at = 10, frames = 5 }]
+/// profile = { display_wait = true }
+/// hash = "…"
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    #[serde(rename = "test", default)]
+    pub tests: Vec<TestCase>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    pub name: String,
+    /// ROM or source file
+    pub rom: PathBuf,
+    /// 60 Hz frames to run before checking the display
+    pub frames: u64,
+    /// Speed and quirk settings
+    #[serde(default)]
+    pub profile: MovieSettings,
+    /// Scripted key presses
+    #[serde(default)]
+    pub press: Vec<KeyPress>,
+    /// Input movie to play instead of a press script. Its settings replace
+    /// the profile.
+    pub movie: Option<PathBuf>,
+    /// SHA-1 of the final framebuffer, ARGB8888 little endian in the
+    /// default palette
+    pub hash: Option<String>,
+    /// Reference PNG of the final display, at native resolution or a whole
+    /// multiple of it
+    pub image: Option<PathBuf>,
+    /// Skip the test when the ROM is missing, for suites that cannot be
+    /// redistributed
+    #[serde(default)]
+    pub optional: bool,
+}
+
+/// Hold `key` for `frames` frames starting at frame `at`
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct KeyPress {
+    pub key: u8,
+    pub at: u64,
+    #[serde(default = "KeyPress::default_frames")]
+    pub frames: u64,
+}
+
+impl KeyPress {
+    fn default_frames() -> u64 {
+        1
+    }
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Passed,
+    Failed(Vec<String>),
+    Skipped(String),
+    /// No expectation given, the actual hash is reported so one can be added
+    Unverified(String),
+}
+
+/// Number of tests per outcome
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub unverified: usize,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, RegressionError> {
+        let text = std::fs::read_to_string(path).map_err(|source| RegressionError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|source| RegressionError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+/// Run every test of a manifest, printing a line per test. Images of the
+/// actual display and of mismatches are written to `output`.
+pub fn run(manifest_path: &Path, output: &Path) -> Result<Summary, RegressionError> {
+    let manifest = Manifest::load(manifest_path)?;
+    let base = manifest_path.parent().unwrap_or(Path::new("."));
+    let mut summary = Summary::default();
+
+    for test in &manifest.tests {
+        let outcome = run_test(test, base, output);
+        match &outcome {
+            Outcome::Passed => {
+                summary.passed += 1;
+                println!("test {} ... ok", test.name);
+            }
+            Outcome::Failed(reasons) => {
+                summary.failed += 1;
+                println!("test {} ... FAILED", test.name);
+                for reason in reasons {
+                    println!("    {}", reason);
+                }
+            }
+            Outcome::Skipped(reason) => {
+                summary.skipped += 1;
+                println!("test {} ... skipped ({})", test.name, reason);
+            }
+            Outcome::Unverified(hash) => {
+                summary.unverified += 1;
+                println!("test {} ... unverified, hash = \"{}\"", test.name, hash);
+            }
+        }
+    }
+
+    println!(
+        "\n{} passed, {} failed, {} skipped, {} unverified",
+        summary.passed, summary.failed, summary.skipped, summary.unverified
+    );
+    Ok(summary)
+}
+
+fn run_test(test: &TestCase, base: &Path, output: &Path) -> Outcome {
+    let rom = base.join(&test.rom);
+    if test.optional && !rom.exists() {
+        return Outcome::Skipped(format!("{} not found", rom.display()));
+    }
+
+    let mut headless = match start(test, base, &rom) {
+        Ok(headless) => headless,
+        Err(e) => return Outcome::Failed(vec![e]),
+    };
+    if let Err(e) = headless.run_frames(test.frames) {
+        return Outcome::Failed(vec![format!(
+            "emulation error at frame {}: {}",
+            headless.frame, e
+        )]);
+    }
+
+    let emulator = &mut headless.emulator;
+    emulator.frame_changed();
+    let (width, height) = emulator.resolution();
+    let frame = emulator.framebuffer();
+    let hash = framebuffer_hash(frame);
+
+    let mut failures = Vec::new();
+    if let Some(expected) = &test.hash {
+        if !expected.eq_ignore_ascii_case(&hash) {
+            failures.push(format!(
+                "framebuffer hash {} != expected {}",
+                hash, expected
+            ));
+        }
+    }
+    if let Some(image) = &test.image {
+        match compare_image(&base.join(image), frame, width, height) {
+            Ok(None) => {}
+            Ok(Some((differing, diff))) => {
+                let path = output.join(format!("{}.diff.png", test.name));
+                failures.push(format!(
+                    "{} pixel(s) differ from {}, diff: {}",
+                    differing,
+                    image.display(),
+                    path.display()
+                ));
+                if let Err(e) = save(&path, &diff, width, height) {
+                    failures.push(e);
+                }
+            }
+            Err(e) => failures.push(e),
+        }
+    }
+
+    let verified = test.hash.is_some() || test.image.is_some();
+    if !failures.is_empty() || !verified {
+        let path = output.join(format!("{}.actual.png", test.name));
+        match save(&path, frame, width, height) {
+            Ok(()) => failures.push(format!("actual: {}", path.display())),
+            Err(e) => failures.push(e),
+        }
+    }
+
+    match (verified, failures.len()) {
+        (false, _) => Outcome::Unverified(hash),
+        (true, 0) => Outcome::Passed,
+        (true, _) => Outcome::Failed(failures),
+    }
+}
+
+/// Fresh emulator with the test's settings, program and input
+fn start(test: &TestCase, base: &Path, rom: &Path) -> Result<Headless, String> {
+    let movie = test
+        .movie
+        .as_ref()
+        .map(|path| Movie::load(&base.join(path)))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let settings = movie.as_ref().map_or(test.profile, |movie| movie.settings);
+
+    let mut emulator = Chip8Emulator::new();
+    emulator.set_mute(true);
+    emulator.set_policy(settings.policy);
+    emulator.set_display_wait(settings.display_wait);
+    emulator.set_seed(settings.seed);
+    let program = crate::load_file(&mut emulator, rom)?;
+    emulator.reset();
+
+    let movie = match movie {
+        Some(movie) => {
+            movie
+                .verify(emulator.system_name(), &program)
+                .map_err(|e| e.to_string())?;
+            Some(movie)
+        }
+        None if !test.press.is_empty() => {
+            let mut movie = Movie::new(emulator.system_name(), &program, settings);
+            for frame in 0..test.frames {
+                let mut keys = [false; 16];
+                for press in &test.press {
+                    if (press.at..press.at + press.frames).contains(&frame) {
+                        let key = keys
+                            .get_mut(press.key as usize)
+                            .ok_or_else(|| format!("key {:#X} does not exist", press.key))?;
+                        *key = true;
+                    }
+                }
+                movie.push(&keys);
+            }
+            Some(movie)
+        }
+        None => None,
+    };
+
+    let mut headless = Headless::new(
+        emulator,
+        crate::clock(settings.frequency, settings.vip_timing),
+    );
+    if let Some(movie) = movie {
+        headless.play(movie);
+    }
+    Ok(headless)
+}
+
+pub fn framebuffer_hash(frame: &[u32]) -> String {
+    let bytes: Vec<u8> = frame.iter().flat_map(|pixel| pixel.to_le_bytes()).collect();
+    movie::sha1(&bytes)
+}
+
+/// Compare the display against a reference PNG. Returns the number of
+/// differing pixels and a diff image (mismatches red, the rest dimmed), or
+/// `None` when they match.
+fn compare_image(
+    path: &Path,
+    frame: &[u32],
+    width: usize,
+    height: usize,
+) -> Result<Option<(usize, Vec<u32>)>, String> {
+    let ([image_width, image_height], data) =
+        screenshot::load_png(path).map_err(|e| e.to_string())?;
+    let scale = image_width / width;
+    if scale == 0 || image_width != width * scale || image_height != height * scale {
+        return Err(format!(
+            "reference image {}: {}x{} is not a multiple of the {}x{} display",
+            path.display(),
+            image_width,
+            image_height,
+            width,
+            height
+        ));
+    }
+
+    let mut differing = 0;
+    let diff = frame
+        .iter()
+        .enumerate()
+        .map(|(i, &pixel)| {
+            let (x, y) = (i % width * scale, i / width * scale);
+            let offset = (y * image_width + x) * 4;
+            let [r, g, b] = [data[offset], data[offset + 1], data[offset + 2]];
+            let expected = 0xFF00_0000 | u32::from(r) << 16 | u32::from(g) << 8 | u32::from(b);
+            if expected == pixel | 0xFF00_0000 {
+                0xFF00_0000 | (pixel >> 2) & 0x003F_3F3F
+            } else {
+                differing += 1;
+                0xFFFF_0000
+            }
+        })
+        .collect();
+
+    Ok((differing > 0).then_some((differing, diff)))
+}
+
+fn save(path: &Path, pixels: &[u32], width: usize, height: usize) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    screenshot::save_png(path, pixels, width, height, 1).map_err(|e| e.to_string())
+}
@@ -0,0 +1,368 @@
+use crate::emulators::chip8::configs::TIMER_PERIOD;
+use crate::emulators::{EmuError, Emulator};
+use std::time::{Duration, Instant};
+
+/// Longest stretch of emulated time run in one go. After a stall (window
+/// dragged, debugger pause, slow frame) the scheduler catches up to this much
+/// and drops the rest instead of fast-forwarding.
+pub const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
+/// How fast the emulated CPU runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clock {
+    /// Every instruction takes the same time, `hz` instructions per second
+    Instructions { hz: u32 },
+    /// Instructions take as many cycles as the emulator reports, `hz` cycles per second
+    Cycles { hz: u32 },
+}
+
+/// A slice of emulated time: run the CPU until the credit is spent, then
+/// tick the timers if the slice ended on a 60 Hz boundary
+#[derive(Debug, Clone, Copy)]
+pub struct Slice {
+    pub tick_timers: bool,
+}
+
+/// Converts wall-clock time into CPU work and timer ticks, independent of
+/// how often the UI repaints
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    pub clock: Clock,
+    /// Only run whole 60 Hz ticks, leaving partial ones pending. Input then
+    /// only changes between ticks, so runs can be reproduced exactly.
+    pub whole_ticks: bool,
+    last_update: Option<Instant>,
+    /// Wall-clock time not yet turned into slices
+    pending: Duration,
+    /// Time since the last timer tick
+    since_tick: Duration,
+    /// CPU cycles that may still run in the current slice, negative when the
+    /// last instruction overshot
+    credit: f64,
+}
+
+impl Scheduler {
+    pub fn new(clock: Clock) -> Self {
+        Self {
+            clock,
+            whole_ticks: false,
+            last_update: None,
+            pending: Duration::ZERO,
+            since_tick: Duration::ZERO,
+            credit: 0.0,
+        }
+    }
+
+    /// Forget elapsed time, e.g. while paused, so resuming does not catch up
+    pub fn reset(&mut self) {
+        self.last_update = None;
+        self.pending = Duration::ZERO;
+        self.since_tick = Duration::ZERO;
+        self.credit = 0.0;
+    }
+
+    /// Account for the wall-clock time passed since the previous call
+    pub fn advance(&mut self, now: Instant) {
+        if let Some(last) = self.last_update {
+            self.advance_by(now.duration_since(last));
+        }
+        self.last_update = Some(now);
+    }
+
+    /// Account for `elapsed` of emulated time, e.g. one frame of a headless run
+    pub fn advance_by(&mut self, elapsed: Duration) {
+        self.pending = (self.pending + elapsed).min(MAX_CATCH_UP);
+    }
+
+    /// Run the instructions and timer ticks for all pending time, calling
+    /// `on_tick` after every timer tick. Stops early when `on_tick` returns
+    /// false.
+    pub fn run_pending<E: Emulator + ?Sized>(
+        &mut self,
+        emulator: &mut E,
+        mut on_tick: impl FnMut(&mut E) -> bool,
+    ) -> Result<(), EmuError> {
+        while let Some(slice) = self.next_slice() {
+            while self.has_credit() {
+                if emulator.waiting_for_vblank() {
+                    self.idle();
+                    break;
+                }
+                emulator.step()?;
+                self.spend(emulator.last_instruction_cycles());
+            }
+            if slice.tick_timers {
+                emulator.update_timers(TIMER_PERIOD);
+                if !on_tick(emulator) {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Next slice of pending time, up to the next timer tick. The cycles it
+    /// is worth are added to the credit.
+    pub fn next_slice(&mut self) -> Option<Slice> {
+        if self.pending.is_zero() {
+            return None;
+        }
+
+        let until_tick = TIMER_PERIOD.saturating_sub(self.since_tick);
+        if self.whole_ticks && self.pending < until_tick {
+            return None;
+        }
+        let (length, tick_timers) = if self.pending >= until_tick {
+            (until_tick, true)
+        } else {
+            (self.pending, false)
+        };
+
+        self.pending -= length;
+        self.since_tick = if tick_timers {
+            Duration::ZERO
+        } else {
+            self.since_tick + length
+        };
+        self.credit += length.as_secs_f64() * self.hz() as f64;
+
+        Some(Slice { tick_timers })
+    }
+
+    /// Whether the CPU may run another instruction in the current slice
+    pub fn has_credit(&self) -> bool {
+        self.credit > 0.0
+    }
+
+    /// Give up the rest of the current slice, e.g. while the CPU waits for
+    /// vblank, so the time is not made up for in a burst afterwards
+    pub fn idle(&mut self) {
+        self.credit = self.credit.min(0.0);
+    }
+
+    /// Charge an executed instruction that took `cycles` machine cycles
+    pub fn spend(&mut self, cycles: u32) {
+        self.credit -= match self.clock {
+            Clock::Instructions { .. } => 1.0,
+            Clock::Cycles { .. } => cycles as f64,
+        };
+    }
+
+    fn hz(&self) -> u32 {
+        match self.clock {
+            Clock::Instructions { hz } | Clock::Cycles { hz } => hz,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulators::ExecutionPolicy;
+    use std::path::Path;
+
+    /// Counts instructions and timer ticks, every instruction taking `cycles`
+    struct FakeCpu {
+        cycles: u32,
+        steps: u32,
+        ticks: u32,
+        /// Stall for vblank after this many instructions
+        wait_after: Option<u32>,
+    }
+
+    impl FakeCpu {
+        fn new(cycles: u32) -> Self {
+            Self {
+                cycles,
+                steps: 0,
+                ticks: 0,
+                wait_after: None,
+            }
+        }
+    }
+
+    impl Emulator for FakeCpu {
+        type Metadata = ();
+
+        fn system_name(&self) -> &'static str {
+            "Fake"
+        }
+        fn load_rom(&mut self, _path: &Path) -> Result<(), EmuError> {
+            Ok(())
+        }
+        fn load_program(&mut self, _program: &[u8]) -> Result<(), EmuError> {
+            Ok(())
+        }
+        fn reset(&mut self) {}
+        fn step(&mut self) -> Result<(), EmuError> {
+            assert!(
+                !self.waiting_for_vblank(),
+                "stepped while waiting for vblank"
+            );
+            self.steps += 1;
+            Ok(())
+        }
+        fn seed(&self) -> u8 {
+            0
+        }
+        fn set_seed(&mut self, _seed: u8) {}
+        fn set_display_wait(&mut self, _display_wait: bool) {}
+        fn set_palette(&mut self, _colors: [u32; 4]) {}
+        fn set_policy(&mut self, _policy: ExecutionPolicy) {}
+        fn skip_instruction(&mut self) {}
+        fn last_instruction_cycles(&self) -> u32 {
+            self.cycles
+        }
+        fn update_timers(&mut self, delta: Duration) {
+            assert_eq!(delta, TIMER_PERIOD);
+            self.ticks += 1;
+            self.wait_after = None;
+        }
+        fn waiting_for_vblank(&self) -> bool {
+            self.wait_after.is_some_and(|steps| self.steps >= steps)
+        }
+        fn end_vblank_wait(&mut self) {
+            self.wait_after = None;
+        }
+        fn sound_active(&self) -> bool {
+            false
+        }
+        fn set_mute(&mut self, _mute: bool) {}
+        fn set_volume(&mut self, _volume: f32) {}
+        fn memory(&self) -> &[u8] {
+            &[]
+        }
+        fn frame_changed(&mut self) -> bool {
+            false
+        }
+        fn framebuffer(&self) -> &[u32] {
+            &[]
+        }
+        fn resolution(&self) -> (usize, usize) {
+            (0, 0)
+        }
+        fn set_input_state(&mut self, _inputs: &[bool]) {}
+        fn keymap(&self) -> Vec<(usize, String)> {
+            Vec::new()
+        }
+        fn gamepad_map(&self) -> Vec<(usize, String)> {
+            Vec::new()
+        }
+        fn button_names(&self) -> Vec<String> {
+            Vec::new()
+        }
+        fn metadata(&self) -> Self::Metadata {}
+    }
+
+    fn run(scheduler: &mut Scheduler, cpu: &mut FakeCpu, elapsed: Duration) {
+        scheduler.advance_by(elapsed);
+        scheduler.run_pending(cpu, |_| true).unwrap();
+    }
+
+    #[test]
+    fn long_stalls_catch_up_at_most_100_ms() {
+        let mut scheduler = Scheduler::new(Clock::Instructions { hz: 600 });
+        scheduler.whole_ticks = true;
+        let mut cpu = FakeCpu::new(1);
+        run(&mut scheduler, &mut cpu, Duration::from_secs(5));
+        // 100 ms holds six whole ticks
+        assert_eq!(cpu.ticks, 6);
+        assert_eq!(cpu.steps, 60);
+
+        // The dropped time is not made up for later
+        run(&mut scheduler, &mut cpu, TIMER_PERIOD);
+        assert_eq!(cpu.ticks, 7);
+        assert_eq!(cpu.steps, 70);
+    }
+
+    #[test]
+    fn fractions_of_instructions_carry_over_between_calls() {
+        // 1.5 instructions per tick
+        let mut scheduler = Scheduler::new(Clock::Instructions { hz: 90 });
+        let mut cpu = FakeCpu::new(1);
+        let mut per_call = Vec::new();
+        for _ in 0..4 {
+            let before = cpu.steps;
+            run(&mut scheduler, &mut cpu, TIMER_PERIOD);
+            per_call.push(cpu.steps - before);
+        }
+        assert_eq!(per_call, [2, 1, 2, 1]);
+        assert_eq!(cpu.ticks, 4);
+    }
+
+    #[test]
+    fn clocks_count_instructions_or_cycles() {
+        let mut instructions = Scheduler::new(Clock::Instructions { hz: 600 });
+        let mut cycles = Scheduler::new(Clock::Cycles { hz: 600 });
+        let mut by_instruction = FakeCpu::new(3);
+        let mut by_cycle = FakeCpu::new(3);
+
+        run(&mut instructions, &mut by_instruction, TIMER_PERIOD);
+        run(&mut cycles, &mut by_cycle, TIMER_PERIOD);
+        assert_eq!(by_instruction.steps, 10);
+        // 10 cycles per tick, the fourth instruction overshoots
+        assert_eq!(by_cycle.steps, 4);
+
+        for _ in 1..60 {
+            run(&mut instructions, &mut by_instruction, TIMER_PERIOD);
+            run(&mut cycles, &mut by_cycle, TIMER_PERIOD);
+        }
+        assert_eq!((by_instruction.steps, by_instruction.ticks), (600, 60));
+        assert_eq!((by_cycle.steps, by_cycle.ticks), (200, 60));
+    }
+
+    #[test]
+    fn slices_end_on_timer_ticks() {
+        let mut scheduler = Scheduler::new(Clock::Instructions { hz: 1000 });
+        assert!(scheduler.next_slice().is_none());
+
+        scheduler.advance_by(Duration::from_millis(10));
+        assert!(!scheduler.next_slice().unwrap().tick_timers);
+        assert!(scheduler.next_slice().is_none());
+
+        // Reaching the tick splits the time around it
+        scheduler.advance_by(Duration::from_millis(10));
+        assert!(scheduler.next_slice().unwrap().tick_timers);
+        assert!(!scheduler.next_slice().unwrap().tick_timers);
+        assert!(scheduler.next_slice().is_none());
+    }
+
+    #[test]
+    fn whole_ticks_leave_partial_ticks_pending() {
+        let mut scheduler = Scheduler::new(Clock::Instructions { hz: 600 });
+        scheduler.whole_ticks = true;
+        let mut cpu = FakeCpu::new(1);
+        run(&mut scheduler, &mut cpu, TIMER_PERIOD / 2);
+        assert_eq!((cpu.steps, cpu.ticks), (0, 0));
+        run(&mut scheduler, &mut cpu, TIMER_PERIOD / 2);
+        assert_eq!((cpu.steps, cpu.ticks), (10, 1));
+    }
+
+    #[test]
+    fn waiting_for_vblank_gives_up_the_rest_of_the_tick() {
+        let mut scheduler = Scheduler::new(Clock::Instructions { hz: 600 });
+        let mut cpu = FakeCpu::new(1);
+        cpu.wait_after = Some(3);
+        run(&mut scheduler, &mut cpu, TIMER_PERIOD);
+        assert_eq!((cpu.steps, cpu.ticks), (3, 1));
+        // No burst to make up for the idle time
+        run(&mut scheduler, &mut cpu, TIMER_PERIOD);
+        assert_eq!((cpu.steps, cpu.ticks), (13, 2));
+    }
+
+    #[test]
+    fn reset_drops_pending_time_and_credit() {
+        let mut scheduler = Scheduler::new(Clock::Instructions { hz: 90 });
+        let mut cpu = FakeCpu::new(1);
+        run(&mut scheduler, &mut cpu, TIMER_PERIOD + TIMER_PERIOD / 2);
+        scheduler.advance_by(Duration::from_millis(5));
+        scheduler.reset();
+        assert!(scheduler.next_slice().is_none());
+        assert!(!scheduler.has_credit());
+
+        // A fresh tick runs as if from the start
+        let before = cpu.steps;
+        run(&mut scheduler, &mut cpu, TIMER_PERIOD);
+        assert_eq!(cpu.steps - before, 2);
+    }
+}
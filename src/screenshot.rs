@@ -0,0 +1,278 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ScreenshotError {
+    #[error("I/O error while writing '{path:?}': {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Failed to encode PNG '{path:?}': {source}")]
+    Encode {
+        path: PathBuf,
+        #[source]
+        source: png::EncodingError,
+    },
+
+    #[error("Failed to decode PNG '{path:?}': {source}")]
+    Decode {
+        path: PathBuf,
+        #[source]
+        source: png::DecodingError,
+    },
+
+    #[error("Unsupported PNG '{path:?}': {reason}")]
+    Unsupported { path: PathBuf, reason: String },
+}
+
+/// Write an ARGB8888 framebuffer as an RGB PNG, each pixel enlarged to a
+/// `scale` x `scale` block
+pub fn save_png(
+    path: &Path,
+    pixels: &[u32],
+    width: usize,
+    height: usize,
+    scale: usize,
+) -> Result<(), ScreenshotError> {
+    let scale = scale.max(1);
+    let file = File::create(path).map_err(|source| ScreenshotError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let encode_error = |source| ScreenshotError::Encode {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        (width * scale) as u32,
+        (height * scale) as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(encode_error)?;
+
+    let mut data = Vec::with_capacity(width * height * scale * scale * 3);
+    for row in pixels.chunks(width) {
+        let line: Vec<u8> = row
+            .iter()
+            .flat_map(|&argb| {
+                let rgb = [(argb >> 16) as u8, (argb >> 8) as u8, argb as u8];
+                std::iter::repeat_n(rgb, scale).flatten()
+            })
+            .collect();
+        for _ in 0..scale {
+            data.extend_from_slice(&line);
+        }
+    }
+
+    writer.write_image_data(&data).map_err(encode_error)
+}
+
+/// Read a PNG as RGBA8888, returning its width and height in pixels and the
+/// pixel data
+pub fn load_png(path: &Path) -> Result<([usize; 2], Vec<u8>), ScreenshotError> {
+    let file = File::open(path).map_err(|source| ScreenshotError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let decode_error = |source| ScreenshotError::Decode {
+        path: path.to_path_buf(),
+        source,
+    };
+    let unsupported = |reason: String| ScreenshotError::Unsupported {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(decode_error)?;
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| unsupported("too large".to_string()))?;
+    let mut data = vec![0; size];
+    let info = reader.next_frame(&mut data).map_err(decode_error)?;
+    data.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => data,
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF])
+            .collect(),
+        png::ColorType::Grayscale => data.iter().flat_map(|&v| [v, v, v, 0xFF]).collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .flat_map(|va| [va[0], va[0], va[0], va[1]])
+            .collect(),
+        other => return Err(unsupported(format!("colour type {:?}", other))),
+    };
+    Ok(([info.width as usize, info.height as usize], rgba))
+}
+
+/// The most recent screenshot [`timestamped_path`] saved for a ROM, given
+/// the file names in the ROM's directory
+pub fn latest(rom: &Path, names: &[String]) -> Option<PathBuf> {
+    let stem = rom.file_stem()?.to_string_lossy().into_owned();
+    let dir = match rom.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    names
+        .iter()
+        .filter_map(|name| {
+            let rest = name.strip_prefix(&stem)?.strip_prefix('-')?;
+            let rest = rest.strip_suffix(".png")?;
+            let (time, count) = match rest.get(15..) {
+                Some("") => (rest, 1),
+                Some(suffix) => (&rest[..15], suffix.strip_prefix('-')?.parse().ok()?),
+                None => return None,
+            };
+            let is_timestamp =
+                time.chars().enumerate().all(
+                    |(i, c)| {
+                        if i == 8 {
+                            c == '-'
+                        } else {
+                            c.is_ascii_digit()
+                        }
+                    },
+                );
+            // The timestamps sort chronologically
+            is_timestamp.then_some(((time, count), name))
+        })
+        .max()
+        .map(|(_, name)| dir.join(name))
+}
+
+/// `<rom name>-YYYYMMDD-HHMMSS.<extension>` next to the ROM, or in the
+/// working directory when no ROM is loaded. The time is UTC. A `-2`, `-3`,
+/// ... suffix keeps files saved within the same second apart.
+pub fn timestamped_path(rom: Option<&Path>, extension: &str) -> PathBuf {
+    let stem = rom
+        .and_then(|rom| rom.file_stem())
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "screenshot".to_string());
+    let dir = rom.and_then(|rom| rom.parent()).unwrap_or(Path::new(""));
+    unused_path(dir, &stem, &timestamp(SystemTime::now()), extension)
+}
+
+fn unused_path(dir: &Path, stem: &str, time: &str, extension: &str) -> PathBuf {
+    (1u32..)
+        .map(|count| {
+            let name = match count {
+                1 => format!("{}-{}.{}", stem, time, extension),
+                _ => format!("{}-{}-{}.{}", stem, time, count, extension),
+            };
+            dir.join(name)
+        })
+        .find(|path| !path.exists())
+        .expect("some suffix is free")
+}
+
+/// Format a time as `YYYYMMDD-HHMMSS` (UTC)
+fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn latest_picks_the_newest_screenshot_of_the_rom() {
+        let rom = Path::new("roms/Pong.ch8");
+        let found = latest(
+            rom,
+            &names(&[
+                "Pong-20240101-120000.png",
+                "Pong-20250101-120000.png",
+                "Pong-20250101-120000-2.png",
+                "Pong-20250101-120000-10.png",
+                "Pong-20990101-120000.gif",
+                "Pong-backup.png",
+                "Pong2-20990101-120000.png",
+                "Pong-20990101-1200xx.png",
+            ]),
+        );
+        assert_eq!(
+            found,
+            Some(PathBuf::from("roms/Pong-20250101-120000-10.png"))
+        );
+        assert_eq!(latest(rom, &names(&["Pong.ch8", "Pong.png"])), None);
+    }
+
+    #[test]
+    fn timestamped_paths_do_not_overwrite_each_other() {
+        let dir = std::env::temp_dir().join(format!("chip8-screenshot-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let time = "20260101-120000";
+        let mut saved = Vec::new();
+        for _ in 0..3 {
+            let path = unused_path(&dir, "Pong", time, "png");
+            std::fs::write(&path, []).unwrap();
+            saved.push(path.file_name().unwrap().to_string_lossy().into_owned());
+        }
+        assert_eq!(
+            saved,
+            [
+                "Pong-20260101-120000.png",
+                "Pong-20260101-120000-2.png",
+                "Pong-20260101-120000-3.png"
+            ]
+        );
+        // Another kind of file of the same second does not count
+        assert_eq!(
+            unused_path(&dir, "Pong", time, "gif"),
+            dir.join("Pong-20260101-120000.gif")
+        );
+        assert_eq!(
+            latest(&dir.join("Pong.ch8"), &saved),
+            Some(dir.join("Pong-20260101-120000-3.png"))
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn timestamps_are_utc_calendar_dates() {
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(1_709_210_096);
+        assert_eq!(timestamp(time), "20240229-123456");
+    }
+}
@@ -0,0 +1,309 @@
+use crate::display::Palette;
+use crate::gamepad::GamepadMap;
+use crate::keymap::Keymap;
+use crate::movie::MovieSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// How many recently loaded ROMs are remembered
+const MAX_RECENT: usize = 10;
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("No configuration directory available")]
+    NoConfigDir,
+
+    #[error("I/O error on settings file '{path:?}': {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid settings file '{path:?}': {source}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("Failed to serialize settings: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// User preferences, stored as TOML in the platform config directory
+/// (`$XDG_CONFIG_HOME/chip8/settings.toml` on Linux)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub mute: bool,
+    /// Beep volume from 0 to 1
+    pub volume: f32,
+    /// Directory of the last ROM loaded, where file pickers open
+    pub rom_dir: Option<PathBuf>,
+    /// Recently loaded ROMs, most recent first
+    pub recent_roms: Vec<PathBuf>,
+    /// Directories the ROM library shows
+    pub library_dirs: Vec<PathBuf>,
+    /// Configure known ROMs from the ROM database when they are loaded
+    pub use_database: bool,
+    /// Speed and quirks
+    pub profile: MovieSettings,
+    pub layout: Layout,
+    /// Display palette per system, keyed by system name
+    pub palettes: BTreeMap<String, Palette>,
+    /// Key bindings per system, keyed by system name
+    pub keymaps: BTreeMap<String, Keymap>,
+    /// Key bindings for single ROMs, keyed by the ROM's SHA-1. These take
+    /// precedence over the system's bindings.
+    pub rom_keymaps: BTreeMap<String, Keymap>,
+    /// Controller bindings per system, keyed by system name
+    pub gamepad_maps: BTreeMap<String, GamepadMap>,
+    /// Speed and quirks chosen for single ROMs, keyed by the ROM's SHA-1
+    pub rom_profiles: BTreeMap<String, MovieSettings>,
+    /// Never written, because the settings file could not be read and would
+    /// be lost
+    #[serde(skip)]
+    in_memory: bool,
+}
+
+/// Window and panel sizes, in points
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    pub window_size: Option<[f32; 2]>,
+    pub window_position: Option<[f32; 2]>,
+    pub left_panel: Option<f32>,
+    pub right_panel: Option<f32>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mute: false,
+            volume: 1.0,
+            rom_dir: None,
+            recent_roms: Vec::new(),
+            library_dirs: Vec::new(),
+            use_database: true,
+            profile: MovieSettings::default(),
+            layout: Layout::default(),
+            palettes: BTreeMap::new(),
+            keymaps: BTreeMap::new(),
+            rom_keymaps: BTreeMap::new(),
+            gamepad_maps: BTreeMap::new(),
+            rom_profiles: BTreeMap::new(),
+            in_memory: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> Result<PathBuf, SettingsError> {
+        dirs::config_dir()
+            .map(|dir| dir.join("chip8").join("settings.toml"))
+            .ok_or(SettingsError::NoConfigDir)
+    }
+
+    /// Read the settings file. A missing file yields the defaults.
+    pub fn load() -> Result<Self, SettingsError> {
+        Self::load_from(&Self::path()?)
+    }
+
+    fn load_from(path: &Path) -> Result<Self, SettingsError> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(source) => {
+                return Err(SettingsError::Io {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        toml::from_str(&text).map_err(|source| SettingsError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Read the settings file, or use the defaults when it cannot be read.
+    /// An invalid file is moved to `settings.toml.bak` first so that saving
+    /// the defaults does not overwrite it, and when that fails as well the
+    /// settings are kept in memory only. Returns what went wrong, to tell the
+    /// user.
+    pub fn load_or_default() -> (Self, Option<String>) {
+        match Self::path() {
+            Ok(path) => Self::load_or_default_from(&path),
+            Err(e) => (Self::in_memory_defaults(), Some(e.to_string())),
+        }
+    }
+
+    fn load_or_default_from(path: &Path) -> (Self, Option<String>) {
+        let error = match Self::load_from(path) {
+            Ok(settings) => return (settings, None),
+            Err(error) => error,
+        };
+        let backup = path.with_extension("toml.bak");
+        if matches!(error, SettingsError::Parse { .. }) && std::fs::rename(path, &backup).is_ok() {
+            let message = format!(
+                "{}. It was moved to {:?}, default settings are used.",
+                error, backup
+            );
+            return (Self::default(), Some(message));
+        }
+        let message = format!("{}. Default settings are used and not saved.", error);
+        (Self::in_memory_defaults(), Some(message))
+    }
+
+    fn in_memory_defaults() -> Self {
+        Self {
+            in_memory: true,
+            ..Self::default()
+        }
+    }
+
+    /// Whether [`Settings::save`] writes the settings file
+    pub fn is_saved(&self) -> bool {
+        !self.in_memory
+    }
+
+    /// Write the settings file, unless the settings are kept in memory only
+    pub fn save(&self) -> Result<(), SettingsError> {
+        if self.in_memory {
+            return Ok(());
+        }
+        self.save_to(&Self::path()?)
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), SettingsError> {
+        let text = toml::to_string_pretty(self)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|source| SettingsError::Io {
+                path: dir.to_path_buf(),
+                source,
+            })?;
+        }
+        std::fs::write(path, text).map_err(|source| SettingsError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Put a ROM at the top of the recent list
+    pub fn add_recent(&mut self, path: &Path) {
+        self.recent_roms.retain(|recent| recent != path);
+        self.recent_roms.insert(0, path.to_path_buf());
+        self.recent_roms.truncate(MAX_RECENT);
+    }
+
+    /// Palette chosen for a system, or its default
+    pub fn palette(&self, system: &str) -> Palette {
+        self.palettes
+            .get(system)
+            .cloned()
+            .unwrap_or_else(|| Palette::default_for(system))
+    }
+
+    /// Key bindings for a ROM (by SHA-1) on a system, falling back to the
+    /// system's bindings and then the emulator's `defaults`
+    pub fn keymap(&self, system: &str, rom: Option<&str>, defaults: &[(usize, String)]) -> Keymap {
+        let default = Keymap::from_defaults(defaults);
+        rom.and_then(|sha1| self.rom_keymaps.get(sha1))
+            .or_else(|| self.keymaps.get(system))
+            .map(|keymap| keymap.clone().resized(default.buttons()))
+            .unwrap_or(default)
+    }
+
+    /// Controller bindings for a system, or the emulator's `defaults`
+    pub fn gamepad_map(&self, system: &str, defaults: &[(usize, String)]) -> GamepadMap {
+        self.gamepad_maps
+            .get(system)
+            .cloned()
+            .unwrap_or_else(|| GamepadMap::from_defaults(defaults))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_through_toml() {
+        let mut settings = Settings {
+            mute: true,
+            volume: 0.25,
+            rom_dir: Some(PathBuf::from("/roms")),
+            ..Settings::default()
+        };
+        settings.layout.window_size = Some([1200.0, 800.0]);
+        settings.profile.display_wait = true;
+        settings
+            .palettes
+            .insert("CHIP-8".to_string(), Palette::default_for("CHIP-8"));
+
+        let text = toml::to_string_pretty(&settings).unwrap();
+        let parsed: Settings = toml::from_str(&text).unwrap();
+        assert!(parsed.mute);
+        assert_eq!(parsed.volume, 0.25);
+        assert_eq!(parsed.rom_dir, settings.rom_dir);
+        assert_eq!(parsed.layout, settings.layout);
+        assert_eq!(parsed.profile, settings.profile);
+        assert_eq!(parsed.palettes.len(), 1);
+    }
+
+    #[test]
+    fn recent_roms_are_unique_and_bounded() {
+        let mut settings = Settings::default();
+        for i in 0..12 {
+            settings.add_recent(Path::new(&format!("rom{}.ch8", i)));
+        }
+        settings.add_recent(Path::new("rom5.ch8"));
+        assert_eq!(settings.recent_roms.len(), MAX_RECENT);
+        assert_eq!(settings.recent_roms[0], Path::new("rom5.ch8"));
+        assert_eq!(settings.recent_roms[1], Path::new("rom11.ch8"));
+        assert_eq!(
+            settings
+                .recent_roms
+                .iter()
+                .filter(|p| *p == Path::new("rom5.ch8"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn invalid_files_are_kept() {
+        let dir = std::env::temp_dir().join(format!("chip8-settings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.toml");
+        std::fs::write(&path, "[keymaps]\nCHIP-8 = [\"NoSuchKey\"]\n").unwrap();
+
+        let (settings, warning) = Settings::load_or_default_from(&path);
+        assert!(warning.is_some());
+        assert!(settings.is_saved());
+        assert!(!path.exists());
+        let backup = dir.join("settings.toml.bak");
+        assert!(std::fs::read_to_string(&backup)
+            .unwrap()
+            .contains("NoSuchKey"));
+
+        // Unreadable and cannot be moved aside: never written
+        std::fs::create_dir(&path).unwrap();
+        let (settings, warning) = Settings::load_or_default_from(&path);
+        assert!(warning.is_some());
+        assert!(!settings.is_saved());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let parsed: Settings = toml::from_str("mute = true").unwrap();
+        assert!(parsed.mute);
+        assert_eq!(parsed.volume, 1.0);
+        assert_eq!(parsed.profile, MovieSettings::default());
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// A fresh directory for the images of one test
+fn output_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chip8-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn run_manifest(manifest: &Path, output: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chip8"))
+        .arg("test")
+        .arg(manifest)
+        .arg("--output")
+        .arg(output)
+        .output()
+        .expect("failed to run chip8")
+}
+
+#[test]
+fn self_made_roms_match_expectations() {
+    let output = run_manifest(
+        &manifest_dir().join("tests/roms/manifest.toml"),
+        &output_dir("roms"),
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("0 failed, 0 skipped, 0 unverified"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn mismatch_writes_actual_and_diff_images() {
+    let roms = manifest_dir().join("tests/roms");
+    let output = output_dir("mismatch");
+    std::fs::create_dir_all(&output).unwrap();
+    let manifest = output.join("manifest.toml");
+    std::fs::write(
+        &manifest,
+        format!(
+            "[[test]]\nname = \"wrong\"\nrom = {:?}\nframes = 30\nhash = \"0000\"\nimage = {:?}\n",
+            roms.join("keypad.8o"),
+            roms.join("font.png"),
+        ),
+    )
+    .unwrap();
+
+    let result = run_manifest(&manifest, &output);
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(!result.status.success(), "{}", stdout);
+    assert!(stdout.contains("test wrong ... FAILED"), "{}", stdout);
+    assert!(stdout.contains("!= expected 0000"), "{}", stdout);
+    assert!(output.join("wrong.diff.png").is_file());
+    assert!(output.join("wrong.actual.png").is_file());
+}
+
+/// Tests whose ROM is missing are skipped, those present must match their
+/// pinned expectation
+#[test]
+fn timendus_suite_passes_or_is_skipped() {
+    let output = run_manifest(
+        &manifest_dir().join("tests/timendus/manifest.toml"),
+        &output_dir("timendus"),
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains(", 0 failed,") && stdout.contains(", 0 unverified"),
+        "{}",
+        stdout
+    );
+}